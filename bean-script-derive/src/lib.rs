@@ -0,0 +1,234 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, LitStr};
+
+/// Implements `Scope` and `IntoData` for a struct, so it can be used from
+/// bean as a scope.
+///
+/// Fields marked `#[bean(field)]` become functions that return the field
+/// when called without arguments and set it when called with one. Methods
+/// listed in `#[bean(method(...))]` on the struct are called with their
+/// arguments converted, and may take `&self` or `&mut self`. Either can be
+/// renamed with a string, as in `#[bean(field = "name")]` or
+/// `#[bean(method(len = "size"))]`.
+///
+/// Scopes print as `[Name field: value, ...]`, using `#[bean(name = "...")]`
+/// if given. With `#[bean(display)]`, the struct's `Display` impl is used
+/// instead.
+///
+/// Like every `Scope`, the struct must implement `Debug`, so derive it
+/// alongside `BeanScope`. Fields marked `#[bean(field)]` must also implement
+/// `Clone`, `IntoData` and `FromData`.
+///
+/// ```ignore
+/// #[derive(Debug, BeanScope)]
+/// #[bean(method(area))]
+/// struct Rect {
+///     #[bean(field)]
+///     width: f64,
+///     #[bean(field)]
+///     height: f64,
+/// }
+///
+/// impl Rect {
+///     fn area(&self) -> f64 {
+///         self.width * self.height
+///     }
+/// }
+/// ```
+#[proc_macro_derive(BeanScope, attributes(bean))]
+pub fn derive_bean_scope(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	expand(input)
+		.unwrap_or_else(syn::Error::into_compile_error)
+		.into()
+}
+
+struct Options {
+	name: String,
+	display: bool,
+	methods: Vec<(Ident, String)>,
+}
+
+fn parse_options(input: &DeriveInput) -> syn::Result<Options> {
+	let mut options = Options {
+		name: input.ident.to_string(),
+		display: false,
+		methods: Vec::new(),
+	};
+
+	for attr in input.attrs.iter().filter(|a| a.path().is_ident("bean")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("name") {
+				options.name = meta.value()?.parse::<LitStr>()?.value();
+				Ok(())
+			} else if meta.path.is_ident("display") {
+				options.display = true;
+				Ok(())
+			} else if meta.path.is_ident("method") {
+				meta.parse_nested_meta(|method| {
+					let ident = method
+						.path
+						.get_ident()
+						.cloned()
+						.ok_or_else(|| method.error("expected method name"))?;
+					let name = if method.input.peek(syn::Token![=]) {
+						method.value()?.parse::<LitStr>()?.value()
+					} else {
+						ident.to_string()
+					};
+					options.methods.push((ident, name));
+					Ok(())
+				})
+			} else {
+				Err(meta.error("expected `name`, `display` or `method`"))
+			}
+		})?;
+	}
+
+	Ok(options)
+}
+
+/// Fields marked with `#[bean(field)]`, with their bean names.
+fn parse_fields(input: &DeriveInput) -> syn::Result<Vec<(Ident, String)>> {
+	let Data::Struct(data) = &input.data else {
+		return Err(syn::Error::new_spanned(
+			&input.ident,
+			"BeanScope can only be derived for structs",
+		));
+	};
+
+	let mut fields = Vec::new();
+	if let Fields::Named(named) = &data.fields {
+		for field in &named.named {
+			let ident = field.ident.clone().unwrap();
+			for attr in field.attrs.iter().filter(|a| a.path().is_ident("bean")) {
+				attr.parse_nested_meta(|meta| {
+					if !meta.path.is_ident("field") {
+						return Err(meta.error("expected `field`"));
+					}
+					let name = if meta.input.peek(syn::Token![=]) {
+						meta.value()?.parse::<LitStr>()?.value()
+					} else {
+						ident.to_string()
+					};
+					fields.push((ident.clone(), name));
+					Ok(())
+				})?;
+			}
+		}
+	} else if data
+		.fields
+		.iter()
+		.any(|f| f.attrs.iter().any(|a| a.path().is_ident("bean")))
+	{
+		return Err(syn::Error::new_spanned(
+			&input.ident,
+			"#[bean(field)] is only supported on named fields",
+		));
+	}
+
+	Ok(fields)
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+	let options = parse_options(&input)?;
+	let fields = parse_fields(&input)?;
+
+	let ident = &input.ident;
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	let field_idents: Vec<&Ident> = fields.iter().map(|(ident, _)| ident).collect();
+	let field_names: Vec<&String> = fields.iter().map(|(_, name)| name).collect();
+	let method_idents: Vec<&Ident> = options.methods.iter().map(|(ident, _)| ident).collect();
+	let method_names: Vec<&String> = options.methods.iter().map(|(_, name)| name).collect();
+
+	let to_string = if options.display {
+		quote! { format!("{}", self) }
+	} else {
+		let name = &options.name;
+		quote! {
+			let fields: Vec<String> = vec![#(
+				format!(
+					"{}: {}",
+					#field_names,
+					::bean_script::convert::IntoData::into_data(
+						::std::clone::Clone::clone(&self.#field_idents)
+					).to_string()
+				)
+			),*];
+			if fields.is_empty() {
+				format!("[{}]", #name)
+			} else {
+				format!("[{} {}]", #name, fields.join(", "))
+			}
+		}
+	};
+
+	Ok(quote! {
+		impl #impl_generics ::bean_script::scope::Scope for #ident #ty_generics #where_clause {
+			fn has_function(&self, name: &str) -> bool {
+				[#(#field_names,)* #(#method_names,)*].contains(&name)
+			}
+
+			fn get_function(&self, name: &str) -> Option<::bean_script::scope::function::Function> {
+				match name {
+					#(#field_names => Some(::bean_script::scope::function::Function::BuiltIn {
+						callback: ::bean_script::util::Shared::new(|args, _body_fn, scope| {
+							::bean_script::convert::field(#field_names, &scope, args, |this: &mut Self| {
+								&mut this.#field_idents
+							})
+						}),
+					}),)*
+					#(#method_names => Some(::bean_script::scope::function::Function::BuiltIn {
+						callback: ::bean_script::util::Shared::new(|args, _body_fn, scope| {
+							::bean_script::convert::TypedMethod::<Self, _>::call_method(
+								&Self::#method_idents,
+								#method_names,
+								&scope,
+								args,
+							)
+						}),
+					}),)*
+					_ => None,
+				}
+			}
+
+			fn set_function(&mut self, _name: &str, _function: ::bean_script::scope::function::Function) {}
+
+			fn delete_function(&mut self, _name: &str) {}
+
+			fn set_return_value(&mut self, _value: ::bean_script::data::Data) {}
+
+			fn set_if_state(&mut self, _state: ::bean_script::scope::block_scope::IfState) {}
+
+			fn get_function_list(
+				&self,
+			) -> ::std::collections::HashMap<String, ::bean_script::scope::function::Function> {
+				[#(#field_names,)* #(#method_names,)*]
+					.into_iter()
+					.filter_map(|name| Some((String::from(name), self.get_function(name)?)))
+					.collect()
+			}
+
+			fn as_any(&self) -> &dyn ::std::any::Any {
+				self
+			}
+
+			fn as_mut(&mut self) -> &mut dyn ::std::any::Any {
+				self
+			}
+
+			fn to_string(&self) -> String {
+				#to_string
+			}
+		}
+
+		impl #impl_generics ::bean_script::convert::IntoData for #ident #ty_generics #where_clause {
+			fn into_data(self) -> ::bean_script::data::Data {
+				::bean_script::data::Data::Scope(::bean_script::util::make_ref(self))
+			}
+		}
+	})
+}
@@ -0,0 +1,392 @@
+//! Binary format for parsed scripts, written by `beans compile` to `.beanc`
+//! files. A file starts with [`MAGIC`] and the format version as a
+//! little-endian `u16`, followed by the tree. Nodes are written as a tag
+//! byte, their end and start lines, and their fields. Integers are LEB128
+//! varints, and strings are a length followed by UTF-8 bytes.
+
+use crate::{
+	error::{Error, ErrorSource},
+	parser::{Node, PosNode},
+};
+
+pub const MAGIC: &[u8; 5] = b"BEANC";
+/// Changed whenever the encoding of nodes changes. Files with another
+/// version must be compiled again.
+pub const VERSION: u16 = 1;
+
+const FN_CALL: u8 = 0;
+const SCOPE: u8 = 1;
+const PARAMETER_BLOCK: u8 = 2;
+const PROGRAM: u8 = 3;
+const FN_ACCESS: u8 = 4;
+const BOOLEAN: u8 = 5;
+const NUMBER: u8 = 6;
+const STRING: u8 = 7;
+const NAME: u8 = 8;
+const NONE: u8 = 9;
+const COMMENT: u8 = 10;
+const EMPTY_LINE: u8 = 11;
+
+/// Deepest nesting of nodes [`decode`] accepts, so a corrupt file cannot
+/// overflow the stack. [`encode`] refuses deeper trees, so every file it
+/// writes can be read back.
+const MAX_DEPTH: usize = 256;
+
+pub fn encode(tree: &PosNode) -> Result<Vec<u8>, Error> {
+	let mut out = Vec::from(&MAGIC[..]);
+	out.extend_from_slice(&VERSION.to_le_bytes());
+	write_node(&mut out, tree, 1)?;
+	Ok(out)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<PosNode, Error> {
+	if !bytes.starts_with(MAGIC) {
+		return Err(invalid("missing header"));
+	}
+	let mut reader = Reader {
+		bytes,
+		pos: MAGIC.len(),
+		depth: 0,
+	};
+	let version = u16::from_le_bytes([reader.byte()?, reader.byte()?]);
+	if version != VERSION {
+		return Err(Error::new(
+			&format!(
+				"Compiled script has format version {}, but version {} is expected. Compile it again with beans compile.",
+				version, VERSION
+			),
+			ErrorSource::Internal,
+		));
+	}
+
+	let tree = reader.node()?;
+	if reader.pos != bytes.len() {
+		return Err(invalid("unexpected data after the end of the script"));
+	}
+	Ok(tree)
+}
+
+/// Whether bytes look like a compiled script rather than source code.
+pub fn is_compiled(bytes: &[u8]) -> bool {
+	bytes.starts_with(MAGIC)
+}
+
+fn invalid(reason: &str) -> Error {
+	Error::new(
+		&format!("Invalid compiled script: {}.", reason),
+		ErrorSource::Internal,
+	)
+}
+
+fn write_usize(out: &mut Vec<u8>, mut v: usize) {
+	loop {
+		let byte = (v & 0x7f) as u8;
+		v >>= 7;
+		if v == 0 {
+			out.push(byte);
+			break;
+		}
+		out.push(byte | 0x80);
+	}
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+	write_usize(out, s.len());
+	out.extend_from_slice(s.as_bytes());
+}
+
+fn write_body(out: &mut Vec<u8>, body: &[Box<PosNode>], depth: usize) -> Result<(), Error> {
+	write_usize(out, body.len());
+	for node in body {
+		write_node(out, node, depth)?;
+	}
+	Ok(())
+}
+
+fn write_node(out: &mut Vec<u8>, node: &PosNode, depth: usize) -> Result<(), Error> {
+	if depth > MAX_DEPTH {
+		return Err(Error::new(
+			&format!(
+				"Script nests more than {} levels deep, which compiled scripts do not support.",
+				MAX_DEPTH
+			),
+			ErrorSource::Line(node.ln),
+		));
+	}
+	let tag = match &node.node {
+		Node::FnCall { .. } => FN_CALL,
+		Node::Scope { .. } => SCOPE,
+		Node::ParameterBlock { .. } => PARAMETER_BLOCK,
+		Node::Program { .. } => PROGRAM,
+		Node::FnAccess { .. } => FN_ACCESS,
+		Node::Boolean(_) => BOOLEAN,
+		Node::Number(_) => NUMBER,
+		Node::String(_) => STRING,
+		Node::Name(_) => NAME,
+		Node::None => NONE,
+		Node::Comment(_) => COMMENT,
+		Node::EmptyLine => EMPTY_LINE,
+	};
+	out.push(tag);
+	write_usize(out, node.ln);
+	write_usize(out, node.start_ln);
+
+	match &node.node {
+		Node::FnCall {
+			name,
+			parameters,
+			body_fn,
+			doc,
+		} => {
+			write_str(out, name);
+			write_body(out, parameters, depth + 1)?;
+			match body_fn {
+				Some(body_fn) => {
+					out.push(1);
+					write_node(out, body_fn, depth + 1)?;
+				}
+				None => out.push(0),
+			}
+			match doc {
+				Some(doc) => {
+					out.push(1);
+					write_str(out, doc);
+				}
+				None => out.push(0),
+			}
+		}
+		Node::Scope { body } | Node::ParameterBlock { body } | Node::Program { body } => {
+			write_body(out, body, depth + 1)?
+		}
+		Node::FnAccess { target, call } => {
+			write_node(out, target, depth + 1)?;
+			write_node(out, call, depth + 1)?;
+		}
+		Node::Boolean(v) => out.push(*v as u8),
+		Node::Number(v) => out.extend_from_slice(&v.to_le_bytes()),
+		Node::String(s) | Node::Name(s) | Node::Comment(s) => write_str(out, s),
+		Node::None | Node::EmptyLine => {}
+	}
+	Ok(())
+}
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+	pos: usize,
+	depth: usize,
+}
+
+impl Reader<'_> {
+	fn byte(&mut self) -> Result<u8, Error> {
+		let byte = *self
+			.bytes
+			.get(self.pos)
+			.ok_or_else(|| invalid("unexpected end of file"))?;
+		self.pos += 1;
+		Ok(byte)
+	}
+
+	fn take(&mut self, len: usize) -> Result<&[u8], Error> {
+		let end = self
+			.pos
+			.checked_add(len)
+			.filter(|end| *end <= self.bytes.len())
+			.ok_or_else(|| invalid("unexpected end of file"))?;
+		let slice = &self.bytes[self.pos..end];
+		self.pos = end;
+		Ok(slice)
+	}
+
+	fn usize(&mut self) -> Result<usize, Error> {
+		let mut v: usize = 0;
+		let mut shift = 0;
+		loop {
+			let byte = self.byte()?;
+			if shift >= usize::BITS {
+				return Err(invalid("integer too large"));
+			}
+			v |= ((byte & 0x7f) as usize) << shift;
+			if byte & 0x80 == 0 {
+				return Ok(v);
+			}
+			shift += 7;
+		}
+	}
+
+	fn bool(&mut self) -> Result<bool, Error> {
+		match self.byte()? {
+			0 => Ok(false),
+			1 => Ok(true),
+			_ => Err(invalid("invalid boolean")),
+		}
+	}
+
+	fn string(&mut self) -> Result<String, Error> {
+		let len = self.usize()?;
+		let bytes = self.take(len)?;
+		String::from_utf8(bytes.to_vec()).map_err(|_| invalid("invalid string"))
+	}
+
+	fn list<T>(
+		&mut self,
+		mut item: impl FnMut(&mut Self) -> Result<T, Error>,
+	) -> Result<Vec<T>, Error> {
+		let len = self.usize()?;
+		// Every item takes at least one byte, so a longer list cannot fit.
+		if len > self.bytes.len() - self.pos {
+			return Err(invalid("unexpected end of file"));
+		}
+		(0..len).map(|_| item(self)).collect()
+	}
+
+	fn boxed_node(&mut self) -> Result<Box<PosNode>, Error> {
+		self.node().map(Box::new)
+	}
+
+	fn node(&mut self) -> Result<PosNode, Error> {
+		if self.depth == MAX_DEPTH {
+			return Err(invalid("nodes nested too deeply"));
+		}
+		self.depth += 1;
+		let node = self.node_contents();
+		self.depth -= 1;
+		node
+	}
+
+	fn node_contents(&mut self) -> Result<PosNode, Error> {
+		let tag = self.byte()?;
+		let ln = self.usize()?;
+		let start_ln = self.usize()?;
+
+		let node = match tag {
+			FN_CALL => Node::FnCall {
+				name: self.string()?,
+				parameters: self.list(Self::boxed_node)?,
+				body_fn: if self.bool()? {
+					Some(self.boxed_node()?)
+				} else {
+					None
+				},
+				doc: if self.bool()? {
+					Some(self.string()?)
+				} else {
+					None
+				},
+			},
+			SCOPE => Node::Scope { body: self.list(Self::boxed_node)? },
+			PARAMETER_BLOCK => Node::ParameterBlock { body: self.list(Self::boxed_node)? },
+			PROGRAM => Node::Program { body: self.list(Self::boxed_node)? },
+			FN_ACCESS => Node::FnAccess {
+				target: self.boxed_node()?,
+				call: self.boxed_node()?,
+			},
+			BOOLEAN => Node::Boolean(self.bool()?),
+			NUMBER => {
+				let bytes = self.take(8)?;
+				Node::Number(f64::from_le_bytes(bytes.try_into().unwrap()))
+			}
+			STRING => Node::String(self.string()?),
+			NAME => Node::Name(self.string()?),
+			NONE => Node::None,
+			COMMENT => Node::Comment(self.string()?),
+			EMPTY_LINE => Node::EmptyLine,
+			_ => return Err(invalid("unknown node")),
+		};
+
+		Ok(PosNode { node, ln, start_ln })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{lexer, parser};
+
+	fn parse(code: &str) -> PosNode {
+		parser::parse(lexer::tokenize(String::from(code))).unwrap()
+	}
+
+	#[test]
+	fn round_trips_test_scripts() {
+		let mut paths: Vec<_> = ["test", "test/my_module"]
+			.into_iter()
+			.flat_map(|dir| std::fs::read_dir(dir).unwrap())
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.extension().is_some_and(|ext| ext == "bean"))
+			.collect();
+		paths.sort();
+		assert!(!paths.is_empty());
+
+		for path in paths {
+			let tree = parse(&std::fs::read_to_string(&path).unwrap());
+			let decoded = decode(&encode(&tree).unwrap()).unwrap();
+			assert_eq!(format!("{:?}", decoded), format!("{:?}", tree), "{}", path.display());
+		}
+	}
+
+	#[test]
+	fn rejects_invalid_files() {
+		let bytes = encode(&parse("let(<x>): 1.5 // one\nprint(x)")).unwrap();
+		assert!(is_compiled(&bytes));
+		assert!(!is_compiled(b"print(1)"));
+
+		let error = |bytes: &[u8]| decode(bytes).unwrap_err().message().to_string();
+		assert_eq!(error(b"print(1)"), "Invalid compiled script: missing header.");
+		for len in MAGIC.len()..bytes.len() {
+			assert!(decode(&bytes[..len]).is_err(), "decoded {} bytes", len);
+		}
+		assert_eq!(
+			error(&[&bytes[..], &[0]].concat()),
+			"Invalid compiled script: unexpected data after the end of the script."
+		);
+
+		let mut old = bytes.clone();
+		old[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&0u16.to_le_bytes());
+		assert!(error(&old).starts_with("Compiled script has format version 0"));
+	}
+
+	#[test]
+	fn limits_nesting() {
+		let nested = |depth: usize| {
+			let mut tree = PosNode {
+				node: Node::None,
+				ln: 1,
+				start_ln: 1,
+			};
+			for _ in 1..depth {
+				tree = PosNode {
+					node: Node::Scope {
+						body: vec![Box::new(tree)],
+					},
+					ln: 1,
+					start_ln: 1,
+				};
+			}
+			tree
+		};
+
+		assert!(decode(&encode(&nested(MAX_DEPTH)).unwrap()).is_ok());
+		assert_eq!(
+			encode(&nested(MAX_DEPTH + 1)).unwrap_err().message(),
+			"Script nests more than 256 levels deep, which compiled scripts do not support."
+		);
+
+		let header = [&MAGIC[..], &VERSION.to_le_bytes()].concat();
+		let mut bytes = header.clone();
+		for _ in 0..MAX_DEPTH {
+			bytes.extend_from_slice(&[SCOPE, 1, 1, 1]);
+		}
+		bytes.extend_from_slice(&[NONE, 1, 1]);
+		assert_eq!(
+			decode(&bytes).unwrap_err().message(),
+			"Invalid compiled script: nodes nested too deeply."
+		);
+
+		// A corrupt file can claim far more nesting than a parsed script.
+		let mut bytes = header;
+		for _ in 0..100_000 {
+			bytes.extend_from_slice(&[FN_ACCESS, 1, 1]);
+		}
+		assert!(decode(&bytes).is_err());
+	}
+}
@@ -0,0 +1,576 @@
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::{self, Display, Formatter},
+};
+
+use crate::{
+	modules::{registry::ModuleRegistry, Arity},
+	parser::{Node, PosNode},
+	scope::{function::parse_annotation, Scope},
+	util::Lock,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub msg: String,
+	pub ln: usize,
+}
+
+impl Display for Diagnostic {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		match self.severity {
+			Severity::Error => f.write_str("\x1b[31;1merror\x1b[0m: ")?,
+			Severity::Warning => f.write_str("\x1b[33;1mwarn\x1b[0m: ")?,
+		}
+		f.write_str(&self.msg)
+	}
+}
+
+#[derive(Debug)]
+struct Definition {
+	ln: usize,
+	variable: bool,
+	used: bool,
+	arity: Option<Arity>,
+}
+
+#[derive(Debug, Default)]
+struct Block {
+	names: HashMap<String, Definition>,
+	wildcard: bool,
+}
+
+struct Checker {
+	builtins: HashMap<String, Option<Arity>>,
+	members: HashSet<String>,
+	blocks: Vec<Block>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+/// Statically checks a parsed program without evaluating it. Names are
+/// resolved against the runtime builtins of `registry` and the `fn`, `let`,
+/// `const` and `use` definitions of the script.
+pub fn check(tree: &PosNode, registry: &ModuleRegistry) -> Vec<Diagnostic> {
+	let runtime = registry.runtime();
+	let runtime = Lock::borrow(&runtime);
+	let builtins = runtime
+		.get_function_list()
+		.into_keys()
+		.map(|name| {
+			let arity = runtime.arity(&name);
+			(name, arity)
+		})
+		.collect();
+	let mut members = HashSet::new();
+	collect_members(tree, &mut members);
+	let mut checker = Checker {
+		builtins,
+		members,
+		blocks: Vec::new(),
+		diagnostics: Vec::new(),
+	};
+
+	if let Node::Program { body } = &tree.node {
+		checker.check_block(body, Vec::new(), false);
+	} else {
+		checker.check_node(tree);
+	}
+
+	checker.diagnostics.sort_by_key(|d| d.ln);
+	checker.diagnostics
+}
+
+/// Collects every name accessed with the dot operator, since variables
+/// defined inside a scope object are used through it.
+fn collect_members(pos_node: &PosNode, members: &mut HashSet<String>) {
+	match &pos_node.node {
+		Node::FnCall {
+			name,
+			parameters,
+			body_fn,
+			..
+		} => {
+			if name == "name" {
+				// `name("x")` creates names dynamically, so anything may be used.
+				members.insert(String::from("*"));
+			}
+			for n in parameters.iter().chain(body_fn) {
+				collect_members(n, members);
+			}
+		}
+		Node::Scope { body } | Node::ParameterBlock { body } | Node::Program { body } => {
+			for n in body {
+				collect_members(n, members);
+			}
+		}
+		Node::FnAccess { target, call } => {
+			if let Node::FnCall { name, .. } = &call.node {
+				members.insert(name.clone());
+			}
+			collect_members(target, members);
+			collect_members(call, members);
+		}
+		_ => (),
+	}
+}
+
+pub(crate) fn name_literal(node: &PosNode) -> Option<&String> {
+	match &node.node {
+		Node::Name(name) => Some(name),
+		Node::ParameterBlock { body } if body.len() == 1 => name_literal(&body[0]),
+		_ => None,
+	}
+}
+
+pub(crate) fn string_literal(node: &PosNode) -> Option<&String> {
+	match &node.node {
+		Node::String(string) => Some(string),
+		Node::ParameterBlock { body } if body.len() == 1 => string_literal(&body[0]),
+		_ => None,
+	}
+}
+
+fn strip_annotation(name: &str) -> String {
+	parse_annotation(name)
+		.map(|(name, _)| name)
+		.unwrap_or_else(|_| String::from(name.trim()))
+}
+
+/// Returns the names declared by the parameters of `fn` or `lambda`, along
+/// with the arity they imply.
+fn declared_parameters(parameters: &[Box<PosNode>]) -> (Vec<String>, Arity) {
+	let mut names = Vec::new();
+	let mut required = 0;
+	let mut max = Some(0);
+
+	for parameter in parameters {
+		if let Some(name) = name_literal(parameter) {
+			names.push(strip_annotation(name));
+			required += 1;
+			max = max.map(|m| m + 1);
+		} else if let Node::ParameterBlock { body } = &parameter.node {
+			if let Some(Node::FnCall {
+				name, parameters, ..
+			}) = body.first().map(|n| &n.node)
+			{
+				if let Some(param_name) = parameters.first().and_then(|p| name_literal(p)) {
+					names.push(strip_annotation(param_name));
+					match name.as_str() {
+						"opt" => max = max.map(|m| m + 1),
+						"rest" => max = None,
+						_ => (),
+					}
+				}
+			}
+		}
+	}
+
+	(names, (required, max))
+}
+
+impl Checker {
+	fn report(&mut self, severity: Severity, msg: String, ln: usize) {
+		self.diagnostics.push(Diagnostic { severity, msg, ln });
+	}
+
+	fn lookup(&mut self, name: &str) -> Option<&mut Definition> {
+		self.blocks
+			.iter_mut()
+			.rev()
+			.find_map(|block| block.names.get_mut(name))
+	}
+
+	fn define(&mut self, name: String, definition: Definition) {
+		if let Some(block) = self.blocks.last_mut() {
+			block.names.entry(name).or_insert(definition);
+		}
+	}
+
+	/// Registers the definitions made directly inside a block before any of
+	/// its statements are checked, since function bodies may refer to names
+	/// defined further down.
+	fn hoist(&mut self, body: &[Box<PosNode>]) {
+		for statement in body {
+			let Node::FnCall {
+				name,
+				parameters,
+				body_fn,
+				..
+			} = &statement.node
+			else {
+				continue;
+			};
+
+			match name.as_str() {
+				"fn" | "let" | "const" => {
+					let Some(defined) = parameters.first().and_then(|p| name_literal(p)) else {
+						continue;
+					};
+					let arity = if name == "fn" && body_fn.is_some() && parameters.len() > 1 {
+						Some(declared_parameters(&parameters[1..]).1)
+					} else {
+						None
+					};
+					self.define(
+						strip_annotation(defined),
+						Definition {
+							ln: statement.ln,
+							variable: name != "fn",
+							used: false,
+							arity,
+						},
+					);
+				}
+				"use" | "include" => {
+					let path = parameters.first().and_then(|p| string_literal(p));
+					let alias = parameters.get(1).and_then(|p| name_literal(p));
+					let defined = match (path, alias) {
+						(_, Some(alias)) => Some(alias.clone()),
+						(Some(path), None) => match path.split_once(':') {
+							Some((_, "*")) => None,
+							Some((_, "")) => continue,
+							Some((_, target)) => Some(String::from(target)),
+							None => path.rsplit('/').next().map(String::from),
+						},
+						(None, None) => None,
+					};
+
+					match defined {
+						Some(defined) if name == "use" => self.define(
+							defined,
+							Definition {
+								ln: statement.ln,
+								variable: false,
+								used: true,
+								arity: None,
+							},
+						),
+						_ => {
+							if let Some(block) = self.blocks.last_mut() {
+								block.wildcard = true;
+							}
+						}
+					}
+				}
+				_ => (),
+			}
+		}
+	}
+
+	fn check_block(&mut self, body: &[Box<PosNode>], bound: Vec<String>, is_scope: bool) {
+		self.blocks.push(Block::default());
+		for name in bound {
+			self.define(
+				name,
+				Definition {
+					ln: 0,
+					variable: false,
+					used: true,
+					arity: None,
+				},
+			);
+		}
+		self.hoist(body);
+
+		let mut returned = false;
+		for statement in body {
+			if returned {
+				self.report(
+					Severity::Warning,
+					String::from("Unreachable code after return."),
+					statement.ln,
+				);
+				returned = false;
+			}
+			self.check_node(statement);
+
+			if is_scope {
+				if let Node::FnCall { name, .. } = &statement.node {
+					if name == "return" && !self.is_defined(name) {
+						returned = true;
+					}
+				}
+			}
+		}
+
+		let block = self.blocks.pop().unwrap_or_default();
+		for (name, definition) in block.names {
+			if definition.variable
+				&& !definition.used
+				&& !name.starts_with('_')
+				&& !self.members.contains(&name)
+				&& !self.members.contains("*")
+			{
+				self.report(
+					Severity::Warning,
+					format!("Unused variable {}.", name),
+					definition.ln,
+				);
+			}
+		}
+	}
+
+	fn is_defined(&self, name: &str) -> bool {
+		self.blocks
+			.iter()
+			.any(|block| block.names.contains_key(name))
+	}
+
+	fn is_wildcard(&self) -> bool {
+		self.blocks.iter().any(|block| block.wildcard)
+	}
+
+	fn check_call(
+		&mut self,
+		name: &str,
+		parameters: &[Box<PosNode>],
+		body_fn: &Option<Box<PosNode>>,
+		ln: usize,
+		is_member: bool,
+	) {
+		if !is_member {
+			let arity = if let Some(definition) = self.lookup(name) {
+				definition.used = true;
+				definition.arity
+			} else if let Some(arity) = self.builtins.get(name) {
+				*arity
+			} else {
+				if !self.is_wildcard() {
+					self.report(
+						Severity::Error,
+						format!("Unknown value or function {}.", name),
+						ln,
+					);
+				}
+				None
+			};
+
+			if let Some((min, max)) = arity {
+				if parameters.len() < min || max.is_some_and(|max| parameters.len() > max) {
+					self.report(
+						Severity::Error,
+						format!(
+							"Function {} expects {} argument{}, but got {}.",
+							name,
+							match max {
+								Some(max) if max == min => min.to_string(),
+								Some(max) => format!("{} to {}", min, max),
+								None => format!("at least {}", min),
+							},
+							if max == Some(1) && min == 1 { "" } else { "s" },
+							parameters.len()
+						),
+						ln,
+					);
+				}
+			}
+		}
+
+		let defines_name =
+			!is_member && matches!(name, "fn" | "let" | "const") && !self.is_defined(name);
+		let mut bound = Vec::new();
+		for (i, parameter) in parameters.iter().enumerate() {
+			if defines_name && i == 0 && name_literal(parameter).is_some() {
+				continue;
+			}
+			self.check_node(parameter);
+		}
+
+		if let Some(body) = body_fn {
+			if defines_name || name == "lambda" {
+				let start = if name == "lambda" { 0 } else { 1 };
+				if name != "let" && name != "const" {
+					bound = declared_parameters(parameters.get(start..).unwrap_or_default()).0;
+				}
+			} else {
+				bound = parameters
+					.iter()
+					.filter_map(|p| name_literal(p))
+					.map(|name| strip_annotation(name))
+					.collect();
+			}
+			if name == "match" && !is_member {
+				bound.push(String::from("case"));
+				bound.push(String::from("default"));
+			}
+
+			match &body.node {
+				Node::Scope { body } => self.check_block(body, bound, true),
+				_ => {
+					self.blocks.push(Block::default());
+					for name in bound {
+						self.define(
+							name,
+							Definition {
+								ln: 0,
+								variable: false,
+								used: true,
+								arity: None,
+							},
+						);
+					}
+					self.check_node(body);
+					self.blocks.pop();
+				}
+			}
+		}
+	}
+
+	fn check_node(&mut self, pos_node: &PosNode) {
+		self.check_node_verbose(pos_node, false);
+	}
+
+	fn check_node_verbose(&mut self, pos_node: &PosNode, is_member: bool) {
+		match &pos_node.node {
+			Node::FnCall {
+				name,
+				parameters,
+				body_fn,
+				..
+			} => self.check_call(name, parameters, body_fn, pos_node.ln, is_member),
+			Node::Scope { body } => self.check_block(body, Vec::new(), true),
+			Node::ParameterBlock { body } | Node::Program { body } => {
+				for n in body {
+					self.check_node(n);
+				}
+			}
+			Node::FnAccess { target, call } => {
+				self.check_node(target);
+				self.check_node_verbose(call, true);
+			}
+			Node::Name(name) => {
+				if let Some(definition) = self.lookup(&strip_annotation(name)) {
+					definition.used = true;
+				}
+			}
+			Node::Boolean(_)
+			| Node::Number(_)
+			| Node::String(_)
+			| Node::None
+			| Node::Comment(_)
+			| Node::EmptyLine => (),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{lexer, modules::registry::RegistryFeatures, parser};
+
+	fn diagnostics(code: &str) -> Vec<(Severity, String, usize)> {
+		let tree = parser::parse(lexer::tokenize(String::from(code))).unwrap();
+		let registry = ModuleRegistry::new(RegistryFeatures::default());
+		check(&tree, &registry)
+			.into_iter()
+			.map(|d| (d.severity, d.msg, d.ln))
+			.collect()
+	}
+
+	#[test]
+	fn accepts_valid_code() {
+		let code = "fn(<twice>, <x>): *(x, 2)\nlet(<y>): twice(3)\nprint(y, +(y, 1))";
+		assert!(diagnostics(code).is_empty());
+	}
+
+	#[test]
+	fn reports_unknown_names() {
+		assert_eq!(
+			diagnostics("print(missing)\nfn(<f>): helper"),
+			vec![
+				(
+					Severity::Error,
+					String::from("Unknown value or function missing."),
+					1
+				),
+				(
+					Severity::Error,
+					String::from("Unknown value or function helper."),
+					2
+				),
+			]
+		);
+		// Names defined later in the same block can be used in bodies.
+		assert!(diagnostics("fn(<f>): g\nfn(<g>): 1\nf").is_empty());
+		// A wildcard include may define anything.
+		assert!(diagnostics("include(\"std/math:*\")\nprint(anything)").is_empty());
+	}
+
+	#[test]
+	fn reports_arity_errors() {
+		assert_eq!(
+			diagnostics("sub(1)\nifv(true, 1)\nlist(1, 2, 3)\nstr(1, 2)"),
+			vec![
+				(
+					Severity::Error,
+					String::from("Function sub expects 2 arguments, but got 1."),
+					1
+				),
+				(
+					Severity::Error,
+					String::from("Function ifv expects 3 arguments, but got 2."),
+					2
+				),
+				(
+					Severity::Error,
+					String::from("Function str expects 0 to 1 arguments, but got 2."),
+					4
+				),
+			]
+		);
+		assert_eq!(
+			diagnostics("fn(<f>, <a>, opt(<b>, 1)): +(a, b)\nf(1)\nf(1, 2, 3)\nf"),
+			vec![
+				(
+					Severity::Error,
+					String::from("Function f expects 1 to 2 arguments, but got 3."),
+					3
+				),
+				(
+					Severity::Error,
+					String::from("Function f expects 1 to 2 arguments, but got 0."),
+					4
+				),
+			]
+		);
+	}
+
+	#[test]
+	fn every_runtime_builtin_has_an_arity() {
+		let registry = ModuleRegistry::new(RegistryFeatures::default());
+		let runtime = registry.runtime();
+		let runtime = Lock::borrow(&runtime);
+		for name in runtime.get_function_list().keys() {
+			assert!(runtime.arity(name).is_some(), "{} has no arity", name);
+		}
+	}
+
+	#[test]
+	fn reports_unused_variables() {
+		assert_eq!(
+			diagnostics("let(<x>): 1\nlet(<_y>): 2\nconst(<z>): 3\nprint(z)"),
+			vec![(Severity::Warning, String::from("Unused variable x."), 1)]
+		);
+		// Variables read through the dot operator count as used.
+		assert!(diagnostics("let(<s>): {\n\tlet(<v>): 1\n}\nprint(s.v)").is_empty());
+	}
+
+	#[test]
+	fn reports_unreachable_code() {
+		assert_eq!(
+			diagnostics("fn(<f>): {\n\treturn(1)\n\tprint(2)\n}\nf"),
+			vec![(
+				Severity::Warning,
+				String::from("Unreachable code after return."),
+				3
+			)]
+		);
+	}
+}
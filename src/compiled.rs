@@ -0,0 +1,137 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
+
+use crate::{
+	beanc,
+	data::Data,
+	error::{BeanResult, Error, ErrorSource},
+	evaluator, lexer,
+	parser::{self, PosNode},
+	scope::ScopeRef,
+	util::{make_ref, MutRc, Shared},
+};
+
+/// A parsed script, which can be evaluated any number of times against
+/// fresh scopes, in any registry, without being tokenized and parsed again.
+/// Clones share the same tree.
+#[derive(Clone, Debug)]
+pub struct CompiledScript {
+	tree: Shared<PosNode>,
+	path: Option<PathBuf>,
+}
+
+impl CompiledScript {
+	pub fn compile(code: &str) -> Result<Self, Error> {
+		let tree = parser::parse(lexer::tokenize(String::from(code)))?;
+		Ok(Self::from_tree(tree))
+	}
+
+	/// Compiles a file, or loads a script compiled with `beans compile`.
+	/// Errors from running the script are traced to the file.
+	pub fn compile_file(path: impl AsRef<Path>) -> Result<Self, Error> {
+		let path = path.as_ref();
+		let bytes = fs::read(path).map_err(|e| {
+			Error::new(
+				&format!("Error reading file {}: {}", path.display(), e),
+				ErrorSource::Internal,
+			)
+		})?;
+		let mut script = if beanc::is_compiled(&bytes) {
+			Self::from_bytes(&bytes)?
+		} else {
+			let code = String::from_utf8(bytes).map_err(|_| {
+				Error::new(
+					&format!("Error reading file {}: File is not valid UTF-8.", path.display()),
+					ErrorSource::Internal,
+				)
+			})?;
+			Self::compile(&code)?
+		};
+		script.path = Some(path.to_path_buf());
+		Ok(script)
+	}
+
+	/// Loads a script from the binary format written by
+	/// [`CompiledScript::to_bytes`].
+	pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+		Ok(Self::from_tree(beanc::decode(bytes)?))
+	}
+
+	/// Encodes the tree in the binary format of `.beanc` files. Fails if the
+	/// tree is nested too deeply to be loaded again.
+	pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+		beanc::encode(&self.tree)
+	}
+
+	pub fn from_tree(tree: PosNode) -> Self {
+		Self {
+			tree: Shared::new(tree),
+			path: None,
+		}
+	}
+
+	pub fn tree(&self) -> &PosNode {
+		&self.tree
+	}
+
+	/// The file the script was compiled from, if any.
+	pub fn path(&self) -> Option<&Path> {
+		self.path.as_deref()
+	}
+
+	/// Evaluates the script in a scope, returning the value of its last
+	/// statement.
+	pub fn run(&self, scope: ScopeRef) -> Result<Data, Error> {
+		let result = evaluator::evaluate(&self.tree, scope);
+		match &self.path {
+			Some(path) => result.trace(ErrorSource::File(path.to_string_lossy().to_string())),
+			None => result,
+		}
+	}
+}
+
+/// Compiled files by path, recompiled when a file changes on disk. Clones
+/// share the same cache, so one cache can be given to several registries.
+#[derive(Clone, Debug)]
+pub struct ScriptCache {
+	scripts: MutRc<HashMap<PathBuf, (Option<SystemTime>, CompiledScript)>>,
+}
+
+impl ScriptCache {
+	pub fn new() -> Self {
+		Self {
+			scripts: make_ref(HashMap::new()),
+		}
+	}
+
+	/// The compiled script for a file, compiling it if it is not cached or
+	/// has been modified since it was compiled.
+	pub fn get_or_compile(&self, path: &Path) -> Result<CompiledScript, Error> {
+		let modified = fs::metadata(path).and_then(|m| m.modified()).ok();
+		if let Some((time, script)) = self.scripts.borrow().get(path) {
+			if modified.is_some() && *time == modified {
+				return Ok(script.clone());
+			}
+		}
+
+		let script = CompiledScript::compile_file(path)?;
+		self.scripts
+			.borrow_mut()
+			.insert(path.to_path_buf(), (modified, script.clone()));
+		Ok(script)
+	}
+
+	pub fn clear(&self) {
+		self.scripts.borrow_mut().clear();
+	}
+}
+
+impl Default for ScriptCache {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,652 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+	data::{Data, DataType},
+	error::{Error, ErrorSource},
+	modules::bean_std::collections::{List, Map},
+	scope::{function::Function, ScopeRef},
+	util::{make_ref, Lock},
+};
+
+/// Converts a Rust value into a bean value.
+pub trait IntoData {
+	fn into_data(self) -> Data;
+}
+
+/// Converts a bean value into a Rust value, failing when the value has the
+/// wrong type.
+pub trait FromData: Sized {
+	/// Type of bean values accepted, used in error messages.
+	fn data_type() -> DataType;
+
+	fn from_data(data: Data) -> Result<Self, Error>;
+}
+
+/// Error for a value that cannot be converted into `T`.
+pub fn type_error<T: FromData>(data: &Data) -> Error {
+	Error::new(
+		&format!(
+			"Expected {}, but got {} instead.",
+			T::data_type().to_string(),
+			data.get_type().to_string()
+		),
+		ErrorSource::Internal,
+	)
+}
+
+/// Arguments of a function call, from a list of values or a tuple of values
+/// that convert into data.
+pub trait IntoArgs {
+	fn into_args(self) -> Vec<Data>;
+}
+
+impl IntoArgs for Vec<Data> {
+	fn into_args(self) -> Vec<Data> {
+		self
+	}
+}
+
+macro_rules! impl_into_args {
+	($($t:ident),*) => {
+		impl<$($t: IntoData),*> IntoArgs for ($($t,)*) {
+			#[allow(non_snake_case)]
+			fn into_args(self) -> Vec<Data> {
+				let ($($t,)*) = self;
+				vec![$($t.into_data()),*]
+			}
+		}
+	};
+}
+
+impl_into_args!();
+impl_into_args!(A);
+impl_into_args!(A, B);
+impl_into_args!(A, B, C);
+impl_into_args!(A, B, C, D);
+impl_into_args!(A, B, C, D, E);
+impl_into_args!(A, B, C, D, E, F);
+
+impl IntoData for Data {
+	fn into_data(self) -> Data {
+		self
+	}
+}
+
+impl FromData for Data {
+	fn data_type() -> DataType {
+		DataType::Any
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		Ok(data)
+	}
+}
+
+impl IntoData for () {
+	fn into_data(self) -> Data {
+		Data::None
+	}
+}
+
+/// Accepts any value, discarding it.
+impl FromData for () {
+	fn data_type() -> DataType {
+		DataType::Any
+	}
+
+	fn from_data(_data: Data) -> Result<Self, Error> {
+		Ok(())
+	}
+}
+
+impl IntoData for bool {
+	fn into_data(self) -> Data {
+		Data::Boolean(self)
+	}
+}
+
+impl FromData for bool {
+	fn data_type() -> DataType {
+		DataType::Boolean
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::Boolean(v) => Ok(v),
+			data => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+impl IntoData for f64 {
+	fn into_data(self) -> Data {
+		Data::Number(self)
+	}
+}
+
+impl FromData for f64 {
+	fn data_type() -> DataType {
+		DataType::Number
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::Number(v) => Ok(v),
+			data => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+impl IntoData for f32 {
+	fn into_data(self) -> Data {
+		Data::Number(self as f64)
+	}
+}
+
+impl FromData for f32 {
+	fn data_type() -> DataType {
+		DataType::Number
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		f64::from_data(data).map(|v| v as f32)
+	}
+}
+
+macro_rules! impl_integer {
+	($($t:ty),*) => {$(
+		impl IntoData for $t {
+			fn into_data(self) -> Data {
+				Data::Number(self as f64)
+			}
+		}
+
+		/// Accepts whole numbers in the range of the integer type.
+		impl FromData for $t {
+			fn data_type() -> DataType {
+				DataType::Number
+			}
+
+			fn from_data(data: Data) -> Result<Self, Error> {
+				// MAX rounds up to the next power of two as an f64 for 64-bit
+				// types, so compare against that power of two exclusively.
+				let bits = if <$t>::MIN == 0 { <$t>::BITS } else { <$t>::BITS - 1 };
+				let end = 2f64.powi(bits as i32);
+				match data {
+					Data::Number(v) if v.fract() == 0.0 && v >= <$t>::MIN as f64 && v < end => {
+						Ok(v as $t)
+					}
+					Data::Number(v) => Err(Error::new(
+						&format!(
+							"Expected integer between {} and {}, but got {} instead.",
+							<$t>::MIN,
+							<$t>::MAX,
+							v
+						),
+						ErrorSource::Internal,
+					)),
+					data => Err(type_error::<Self>(&data)),
+				}
+			}
+		}
+	)*};
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoData for String {
+	fn into_data(self) -> Data {
+		Data::String(self)
+	}
+}
+
+impl IntoData for &str {
+	fn into_data(self) -> Data {
+		Data::String(String::from(self))
+	}
+}
+
+impl FromData for String {
+	fn data_type() -> DataType {
+		DataType::String
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::String(v) => Ok(v),
+			data => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+impl IntoData for Function {
+	fn into_data(self) -> Data {
+		Data::Function(Box::new(self))
+	}
+}
+
+impl FromData for Function {
+	fn data_type() -> DataType {
+		DataType::Function
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::Function(v) => Ok(*v),
+			data => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+impl<T: IntoData> IntoData for Option<T> {
+	fn into_data(self) -> Data {
+		match self {
+			Some(v) => v.into_data(),
+			None => Data::None,
+		}
+	}
+}
+
+/// Accepts `none` as `None`, which also makes the argument optional in
+/// [`TypedFunction`]s.
+impl<T: FromData> FromData for Option<T> {
+	fn data_type() -> DataType {
+		DataType::Or(Box::new(T::data_type()), Box::new(DataType::None))
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::None => Ok(None),
+			data => T::from_data(data).map(Some),
+		}
+	}
+}
+
+impl<T: IntoData> IntoData for Vec<T> {
+	fn into_data(self) -> Data {
+		let items = self.into_iter().map(T::into_data).collect();
+		Data::Scope(make_ref(List::new(items, None)))
+	}
+}
+
+impl<T: FromData> FromData for Vec<T> {
+	fn data_type() -> DataType {
+		DataType::List
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		let items = match &data {
+			Data::Scope(scope) => Lock::borrow(scope)
+				.as_any()
+				.downcast_ref::<List>()
+				.map(|list| list.items.clone()),
+			_ => None,
+		};
+		match items {
+			Some(items) => items.into_iter().map(T::from_data).collect(),
+			None => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+impl<K: IntoData, V: IntoData> IntoData for HashMap<K, V> {
+	fn into_data(self) -> Data {
+		let pairs = self
+			.into_iter()
+			.flat_map(|(k, v)| [k.into_data(), v.into_data()])
+			.collect();
+		Data::Scope(make_ref(Map::new(pairs, None)))
+	}
+}
+
+impl<K: FromData + Eq + Hash, V: FromData> FromData for HashMap<K, V> {
+	fn data_type() -> DataType {
+		DataType::Map
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		let hash = match &data {
+			Data::Scope(scope) => Lock::borrow(scope)
+				.as_any()
+				.downcast_ref::<Map>()
+				.map(|map| map.hash.clone()),
+			_ => None,
+		};
+		match hash {
+			Some(hash) => hash
+				.into_iter()
+				.map(|(k, v)| Ok((K::from_data(k)?, V::from_data(v)?)))
+				.collect(),
+			None => Err(type_error::<Self>(&data)),
+		}
+	}
+}
+
+/// Return value of a [`TypedFunction`], either a value or a result.
+pub trait IntoResult {
+	fn into_result(self) -> Result<Data, Error>;
+}
+
+impl<T: IntoData> IntoResult for T {
+	fn into_result(self) -> Result<Data, Error> {
+		Ok(self.into_data())
+	}
+}
+
+impl<T: IntoData> IntoResult for Result<T, Error> {
+	fn into_result(self) -> Result<Data, Error> {
+		self.map(T::into_data)
+	}
+}
+
+fn check_arity(name: &str, arity: usize, given: usize) -> Result<(), Error> {
+	if given > arity {
+		Err(Error::new(
+			&format!(
+				"Function {} expected at most {} argument{}, but got {}.",
+				name,
+				arity,
+				if arity == 1 { "" } else { "s" },
+				given
+			),
+			ErrorSource::Builtin(String::from(name)),
+		))
+	} else {
+		Ok(())
+	}
+}
+
+/// Converts argument number `index` (from 1) of a call to `name`. Missing
+/// arguments are only accepted by types that accept `none`.
+fn argument<T: FromData>(name: &str, index: usize, given: usize, data: Option<Data>) -> Result<T, Error> {
+	match data {
+		Some(data) => T::from_data(data).map_err(|e| {
+			Error::new(
+				&format!("Argument {} of {}: {}", index, name, e.message()),
+				ErrorSource::Builtin(String::from(name)),
+			)
+		}),
+		None => T::from_data(Data::None).map_err(|_| {
+			Error::new(
+				&format!(
+					"Function {} expected at least {} argument{}, but got {}.",
+					name,
+					index,
+					if index == 1 { "" } else { "s" },
+					given
+				),
+				ErrorSource::Builtin(String::from(name)),
+			)
+		}),
+	}
+}
+
+fn wrong_type<S>(name: &str) -> Error {
+	Error::new(
+		&format!(
+			"Tried to call {} on a value that is not {}.",
+			name,
+			std::any::type_name::<S>()
+		),
+		ErrorSource::Builtin(String::from(name)),
+	)
+}
+
+/// A Rust closure callable from bean, whose arguments are converted with
+/// [`FromData`]. `Args` is the tuple of argument types. Register one with
+/// [`ModuleBuilder::typed_function`](crate::modules::ModuleBuilder::typed_function).
+pub trait TypedFunction<Args> {
+	fn call_typed(&self, name: &str, args: Vec<Data>) -> Result<Data, Error>;
+}
+
+/// A value holding an `S` that a [`TypedMethod`] can be called on.
+pub trait Receiver {
+	fn with_ref<S: 'static, R>(&self, f: impl FnOnce(&S) -> R) -> Option<R>;
+	fn with_mut<S: 'static, R>(&self, f: impl FnOnce(&mut S) -> R) -> Option<R>;
+}
+
+impl Receiver for ScopeRef {
+	fn with_ref<S: 'static, R>(&self, f: impl FnOnce(&S) -> R) -> Option<R> {
+		Lock::borrow(self).as_any().downcast_ref::<S>().map(f)
+	}
+
+	fn with_mut<S: 'static, R>(&self, f: impl FnOnce(&mut S) -> R) -> Option<R> {
+		Lock::borrow_mut(self).as_mut().downcast_mut::<S>().map(f)
+	}
+}
+
+/// A Rust method callable on an `S`, taking `&S` or `&mut S` followed by
+/// arguments converted with [`FromData`]. Used by `#[derive(BeanScope)]`
+/// and [`UserType`](crate::user_data::UserType).
+pub trait TypedMethod<S, Args> {
+	fn call_method(&self, name: &str, this: &impl Receiver, args: Vec<Data>) -> Result<Data, Error>;
+}
+
+/// Marks methods taking `&S`.
+pub struct Ref;
+/// Marks methods taking `&mut S`.
+pub struct Mut;
+
+macro_rules! impl_typed_function {
+	($arity:literal; $($t:ident),*) => {
+		impl<Func, R, $($t: FromData),*> TypedFunction<($($t,)*)> for Func
+		where
+			Func: Fn($($t),*) -> R,
+			R: IntoResult,
+		{
+			#[allow(non_snake_case, unused_mut, unused_variables)]
+			fn call_typed(&self, name: &str, args: Vec<Data>) -> Result<Data, Error> {
+				let given = args.len();
+				check_arity(name, $arity, given)?;
+				let mut args = args.into_iter();
+				let mut index = 0;
+				$(
+					index += 1;
+					let $t = argument::<$t>(name, index, given, args.next())?;
+				)*
+				(self)($($t),*).into_result()
+			}
+		}
+
+		impl<S: 'static, Func, R, $($t: FromData),*> TypedMethod<S, (Ref, $($t,)*)> for Func
+		where
+			Func: Fn(&S, $($t),*) -> R,
+			R: IntoResult,
+		{
+			#[allow(non_snake_case, unused_mut, unused_variables)]
+			fn call_method(&self, name: &str, this: &impl Receiver, args: Vec<Data>) -> Result<Data, Error> {
+				let given = args.len();
+				check_arity(name, $arity, given)?;
+				let mut args = args.into_iter();
+				let mut index = 0;
+				$(
+					index += 1;
+					let $t = argument::<$t>(name, index, given, args.next())?;
+				)*
+				this.with_ref(|this| (self)(this, $($t),*))
+					.ok_or_else(|| wrong_type::<S>(name))?
+					.into_result()
+			}
+		}
+
+		impl<S: 'static, Func, R, $($t: FromData),*> TypedMethod<S, (Mut, $($t,)*)> for Func
+		where
+			Func: Fn(&mut S, $($t),*) -> R,
+			R: IntoResult,
+		{
+			#[allow(non_snake_case, unused_mut, unused_variables)]
+			fn call_method(&self, name: &str, this: &impl Receiver, args: Vec<Data>) -> Result<Data, Error> {
+				let given = args.len();
+				check_arity(name, $arity, given)?;
+				let mut args = args.into_iter();
+				let mut index = 0;
+				$(
+					index += 1;
+					let $t = argument::<$t>(name, index, given, args.next())?;
+				)*
+				this.with_mut(|this| (self)(this, $($t),*))
+					.ok_or_else(|| wrong_type::<S>(name))?
+					.into_result()
+			}
+		}
+	};
+}
+
+impl_typed_function!(0;);
+impl_typed_function!(1; A);
+impl_typed_function!(2; A, B);
+impl_typed_function!(3; A, B, C);
+impl_typed_function!(4; A, B, C, D);
+impl_typed_function!(5; A, B, C, D, E);
+impl_typed_function!(6; A, B, C, D, E, F);
+
+/// Accesses a field of a scope of type `S`. Without arguments, returns the
+/// field's value. With one, sets the field and returns its previous value.
+/// Used by `#[derive(BeanScope)]`.
+pub fn field<S: 'static, T: IntoData + FromData + Clone>(
+	name: &str,
+	scope: &ScopeRef,
+	args: Vec<Data>,
+	field: impl FnOnce(&mut S) -> &mut T,
+) -> Result<Data, Error> {
+	let given = args.len();
+	check_arity(name, 1, given)?;
+	let value = args
+		.into_iter()
+		.next()
+		.map(|data| argument::<T>(name, 1, given, Some(data)))
+		.transpose()?;
+
+	scope
+		.with_mut(|this| {
+			let field = field(this);
+			match value {
+				Some(value) => std::mem::replace(field, value),
+				None => field.clone(),
+			}
+		})
+		.map(T::into_data)
+		.ok_or_else(|| wrong_type::<S>(name))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{engine::Engine, modules::ModuleBuilder};
+
+	fn round_trip<T: IntoData + FromData>(value: T) -> T {
+		T::from_data(value.into_data()).unwrap()
+	}
+
+	#[test]
+	fn scalars() {
+		assert!(round_trip(true));
+		assert_eq!(round_trip(1.5f64), 1.5);
+		assert_eq!(round_trip(2.5f32), 2.5);
+		assert_eq!(round_trip(String::from("bean")), "bean");
+		assert_eq!("bean".into_data(), Data::String(String::from("bean")));
+		assert_eq!(().into_data(), Data::None);
+		assert!(String::from_data(Data::Number(1.0)).is_err());
+	}
+
+	#[test]
+	fn integers() {
+		assert_eq!(round_trip(-7i32), -7);
+		assert_eq!(u8::from_data(Data::Number(255.0)).unwrap(), 255);
+		assert_eq!(
+			u8::from_data(Data::Number(256.0)).unwrap_err().message(),
+			"Expected integer between 0 and 255, but got 256 instead."
+		);
+		assert!(i64::from_data(Data::Number(1.5)).is_err());
+		// MAX as f64 rounds up to 2^63 and 2^64, which don't fit.
+		assert!(i64::from_data(Data::Number(i64::MAX as f64)).is_err());
+		assert!(u64::from_data(Data::Number(u64::MAX as f64)).is_err());
+		assert_eq!(i64::from_data(Data::Number(i64::MIN as f64)).unwrap(), i64::MIN);
+		assert_eq!(i64::from_data(Data::Number(2f64.powi(62))).unwrap(), 1 << 62);
+		assert_eq!(u64::from_data(Data::Number(2f64.powi(63))).unwrap(), 1 << 63);
+		assert_eq!(i8::from_data(Data::Number(127.0)).unwrap(), 127);
+		assert!(i8::from_data(Data::Number(128.0)).is_err());
+		assert!(usize::from_data(Data::Boolean(true)).is_err());
+	}
+
+	#[test]
+	fn options() {
+		assert_eq!(Some(3u8).into_data(), Data::Number(3.0));
+		assert_eq!(None::<u8>.into_data(), Data::None);
+		assert_eq!(Option::<f64>::from_data(Data::None).unwrap(), None);
+		assert_eq!(Option::<f64>::from_data(Data::Number(2.0)).unwrap(), Some(2.0));
+		assert!(Option::<f64>::from_data(Data::Boolean(false)).is_err());
+	}
+
+	#[test]
+	fn collections() {
+		let list = vec![1.0, 2.0, 3.0];
+		assert!(DataType::List.matches(&list.clone().into_data()));
+		assert_eq!(round_trip(list.clone()), list);
+
+		let map: HashMap<String, i32> =
+			[(String::from("a"), 1), (String::from("b"), 2)].into_iter().collect();
+		assert!(DataType::Map.matches(&map.clone().into_data()));
+		assert_eq!(round_trip(map.clone()), map);
+
+		assert!(Vec::<f64>::from_data(vec!["x"].into_data()).is_err());
+		assert!(Vec::<f64>::from_data(map.into_data()).is_err());
+	}
+
+	#[test]
+	fn typed_functions() {
+		let add = |a: i32, b: Option<i32>| a + b.unwrap_or(1);
+		assert_eq!(
+			add.call_typed("add", vec![Data::Number(2.0), Data::Number(3.0)]).unwrap(),
+			Data::Number(5.0)
+		);
+		assert_eq!(add.call_typed("add", vec![Data::Number(2.0)]).unwrap(), Data::Number(3.0));
+		assert_eq!(
+			add.call_typed("add", vec![]).unwrap_err().message(),
+			"Function add expected at least 1 argument, but got 0."
+		);
+		assert_eq!(
+			add.call_typed("add", vec![Data::None, Data::None, Data::None])
+				.unwrap_err()
+				.message(),
+			"Function add expected at most 2 arguments, but got 3."
+		);
+		let negate = |v: f64| -v;
+		assert_eq!(
+			negate
+				.call_typed("negate", vec![Data::None, Data::None])
+				.unwrap_err()
+				.message(),
+			"Function negate expected at most 1 argument, but got 2."
+		);
+		assert!(add
+			.call_typed("add", vec![Data::String(String::from("x"))])
+			.unwrap_err()
+			.message()
+			.starts_with("Argument 1 of add:"));
+
+		let fallible = |v: f64| -> Result<f64, Error> {
+			if v < 0.0 {
+				Err(Error::new("Negative.", ErrorSource::Internal))
+			} else {
+				Ok(v.sqrt())
+			}
+		};
+		assert_eq!(fallible.call_typed("sqrt", vec![Data::Number(9.0)]).unwrap(), Data::Number(3.0));
+		assert!(fallible.call_typed("sqrt", vec![Data::Number(-1.0)]).is_err());
+	}
+
+	#[test]
+	fn typed_functions_from_scripts() {
+		fn module(m: &mut ModuleBuilder) {
+			m.typed_function("repeat", |s: String, n: usize| s.repeat(n));
+		}
+		let engine = Engine::new();
+		engine.register_module("text", module);
+		assert_eq!(
+			engine.eval_str("use(\"text\")\ntext.repeat(\"ab\", 3)").unwrap(),
+			Data::String(String::from("ababab"))
+		);
+		assert!(engine.eval_str("text.repeat(\"ab\", -1)").is_err());
+	}
+}
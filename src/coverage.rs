@@ -0,0 +1,191 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	checker::name_literal,
+	data::Data,
+	hook::{self, ExecutionHook},
+	parser::{Node, PosNode},
+	scope::{function::Function, ScopeRef},
+};
+
+#[derive(Debug, Clone)]
+struct FunctionCoverage {
+	name: String,
+	ln: usize,
+	/// Start and end line of the function body.
+	body: (usize, usize),
+	hits: usize,
+}
+
+#[derive(Debug, Clone)]
+struct FileCoverage {
+	source: PathBuf,
+	lines: BTreeMap<usize, usize>,
+	functions: Vec<FunctionCoverage>,
+}
+
+impl FileCoverage {
+	fn new(source: PathBuf, tree: &PosNode) -> Self {
+		let mut file = Self {
+			source,
+			lines: BTreeMap::new(),
+			functions: Vec::new(),
+		};
+		file.add(tree, false);
+		file
+	}
+
+	fn add(&mut self, pos_node: &PosNode, statement: bool) {
+		if statement && !matches!(pos_node.node, Node::Comment(_) | Node::EmptyLine) {
+			self.lines.entry(pos_node.start_ln).or_insert(0);
+		}
+
+		match &pos_node.node {
+			Node::FnCall {
+				name,
+				parameters,
+				body_fn,
+				..
+			} => {
+				if let (true, Some(fn_name), Some(body)) = (
+					name == "fn",
+					parameters.first().and_then(|p| name_literal(p)),
+					body_fn,
+				) {
+					self.functions.push(FunctionCoverage {
+						name: fn_name.clone(),
+						ln: pos_node.start_ln,
+						body: (body.start_ln, body.ln),
+						hits: 0,
+					});
+				}
+				for n in parameters.iter().chain(body_fn) {
+					self.add(n, false);
+				}
+			}
+			Node::Scope { body } | Node::Program { body } => {
+				for n in body {
+					self.add(n, true);
+				}
+			}
+			Node::ParameterBlock { body } => {
+				for n in body {
+					self.add(n, false);
+				}
+			}
+			Node::FnAccess { target, call } => {
+				self.add(target, false);
+				self.add(call, false);
+			}
+			_ => (),
+		}
+	}
+}
+
+/// Records which statements and functions ran in each module file. Register
+/// it as an [`ExecutionHook`]; files loaded through the registry are added
+/// when they load, and the main program with [`Coverage::add_main`].
+#[derive(Debug, Default)]
+pub struct Coverage {
+	files: HashMap<PathBuf, FileCoverage>,
+	/// Source file of modules not keyed by their own path.
+	main: HashMap<PathBuf, PathBuf>,
+}
+
+impl Coverage {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds the main program, whose module is keyed by the directory `dir`
+	/// rather than by `file`. Files that were already added keep their
+	/// counts, so one coverage can be shared by several runs.
+	pub fn add_main(&mut self, dir: PathBuf, file: PathBuf, tree: &PosNode) {
+		self.files
+			.entry(file.clone())
+			.or_insert_with(|| FileCoverage::new(file.clone(), tree));
+		self.main.insert(dir, file);
+	}
+
+	fn file_mut(&mut self, module: &Path) -> Option<&mut FileCoverage> {
+		let source = self.main.get(module).map_or(module, |file| file.as_path());
+		self.files.get_mut(source)
+	}
+
+	/// Fraction of lines that ran in each file, as lines hit and lines found.
+	pub fn summary(&self) -> Vec<(PathBuf, usize, usize)> {
+		let mut files: Vec<&FileCoverage> = self.files.values().collect();
+		files.sort_by(|a, b| a.source.cmp(&b.source));
+		files
+			.iter()
+			.map(|file| {
+				let hit = file.lines.values().filter(|hits| **hits > 0).count();
+				(file.source.clone(), hit, file.lines.len())
+			})
+			.collect()
+	}
+
+	/// Writes the coverage in the lcov tracefile format.
+	pub fn lcov(&self) -> String {
+		let mut files: Vec<&FileCoverage> = self.files.values().collect();
+		files.sort_by(|a, b| a.source.cmp(&b.source));
+
+		let mut lcov = String::new();
+		for file in files {
+			lcov += &format!("TN:\nSF:{}\n", file.source.display());
+			for function in &file.functions {
+				lcov += &format!("FN:{},{}\n", function.ln, function.name);
+			}
+			for function in &file.functions {
+				lcov += &format!("FNDA:{},{}\n", function.hits, function.name);
+			}
+			lcov += &format!(
+				"FNF:{}\nFNH:{}\n",
+				file.functions.len(),
+				file.functions.iter().filter(|f| f.hits > 0).count()
+			);
+			for (ln, hits) in &file.lines {
+				lcov += &format!("DA:{},{}\n", ln, hits);
+			}
+			lcov += &format!(
+				"LF:{}\nLH:{}\nend_of_record\n",
+				file.lines.len(),
+				file.lines.values().filter(|hits| **hits > 0).count()
+			);
+		}
+		lcov
+	}
+}
+
+impl ExecutionHook for Coverage {
+	fn node_enter(&mut self, file: &Path, node: &PosNode, _scope: &ScopeRef, statement: bool) {
+		let Some(file) = self.file_mut(file) else {
+			return;
+		};
+		if statement {
+			*file.lines.entry(node.start_ln).or_insert(0) += 1;
+		}
+	}
+
+	fn call(&mut self, _name: &str, function: &Function, _args: &[Data], _scope: &ScopeRef) {
+		let Function::Custom { body, scope_ref, .. } = function else {
+			return;
+		};
+		let Some(file) = hook::module_path(scope_ref).and_then(|p| self.file_mut(&p)) else {
+			return;
+		};
+		let body = (body.start_ln, body.ln);
+		if let Some(function) = file.functions.iter_mut().find(|f| f.body == body) {
+			function.hits += 1;
+		}
+	}
+
+	fn module_load(&mut self, path: &Path, tree: &PosNode) {
+		self.files
+			.entry(path.to_path_buf())
+			.or_insert_with(|| FileCoverage::new(path.to_path_buf(), tree));
+	}
+}
@@ -0,0 +1,347 @@
+use crate::{
+	error::Error,
+	lexer::{self, Token},
+	parser::{self, PosNode},
+};
+
+/// Location of a token in the source. `start` and `end` are byte offsets,
+/// `ln` and `col` are the one-based line and character column of `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize,
+	pub ln: usize,
+	pub col: usize,
+}
+
+impl Span {
+	pub fn contains(&self, offset: usize) -> bool {
+		self.start <= offset && offset < self.end
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxKind {
+	Whitespace,
+	LineBreak,
+	Comment,
+
+	FnName,
+	FnBody,
+	ArgSeparator,
+	ArgOpen,
+	ArgClose,
+	ScopeOpen,
+	ScopeClose,
+	Accessor,
+
+	Boolean,
+	Number,
+	String,
+	Name,
+	None,
+}
+
+impl SyntaxKind {
+	fn from_token(token: &Token) -> Self {
+		match token {
+			Token::FnName(_) => SyntaxKind::FnName,
+			Token::FnBody => SyntaxKind::FnBody,
+			Token::ArgSeparator => SyntaxKind::ArgSeparator,
+			Token::ArgOpen => SyntaxKind::ArgOpen,
+			Token::ArgClose => SyntaxKind::ArgClose,
+			Token::ScopeOpen => SyntaxKind::ScopeOpen,
+			Token::ScopeClose => SyntaxKind::ScopeClose,
+			Token::Accessor => SyntaxKind::Accessor,
+			Token::Boolean(_) => SyntaxKind::Boolean,
+			Token::Number(_) => SyntaxKind::Number,
+			Token::String(_) => SyntaxKind::String,
+			Token::Name(_) => SyntaxKind::Name,
+			Token::None => SyntaxKind::None,
+			Token::LineBreak | Token::BlankLine | Token::EOF => SyntaxKind::LineBreak,
+			Token::Comment(_) | Token::DocComment(_) => SyntaxKind::Comment,
+		}
+	}
+
+	pub fn is_trivia(&self) -> bool {
+		matches!(
+			self,
+			SyntaxKind::Whitespace | SyntaxKind::LineBreak | SyntaxKind::Comment
+		)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+	pub kind: SyntaxKind,
+	pub text: String,
+	pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyntaxNodeKind {
+	Root,
+	Arguments,
+	Scope,
+}
+
+#[derive(Debug, Clone)]
+pub enum SyntaxElement {
+	Token(SyntaxToken),
+	Node(SyntaxNode),
+}
+
+/// A node of the concrete syntax tree. Brackets and parentheses group their
+/// contents into child nodes; every other token, including whitespace and
+/// comments, is kept in order, so the tree always reproduces its source.
+#[derive(Debug, Clone)]
+pub struct SyntaxNode {
+	pub kind: SyntaxNodeKind,
+	pub children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+	pub fn tokens(&self) -> Vec<&SyntaxToken> {
+		let mut tokens = Vec::new();
+		for child in &self.children {
+			match child {
+				SyntaxElement::Token(token) => tokens.push(token),
+				SyntaxElement::Node(node) => tokens.append(&mut node.tokens()),
+			}
+		}
+		tokens
+	}
+
+	pub fn text(&self) -> String {
+		self.tokens().iter().map(|t| t.text.as_str()).collect()
+	}
+
+	pub fn span(&self) -> Option<Span> {
+		let tokens = self.tokens();
+		let first = tokens.first()?;
+		let last = tokens.last()?;
+		Some(Span {
+			end: last.span.end,
+			..first.span
+		})
+	}
+
+	/// Derives the abstract syntax tree used by the evaluator.
+	pub fn to_ast(&self) -> Result<PosNode, Error> {
+		parser::parse(to_tokens(self.tokens().into_iter()))
+	}
+}
+
+/// Adds a token after the last one, continuing its span.
+fn push_token(tokens: &mut Vec<SyntaxToken>, text: &str, kind: SyntaxKind) {
+	if text.is_empty() {
+		return;
+	}
+	let (start, mut ln, mut col) = tokens
+		.last()
+		.map_or((0, 1, 1), |t| (t.span.end, t.span.ln, t.span.col));
+	for char in tokens.last().map_or("", |t| t.text.as_str()).chars() {
+		if char == '\n' {
+			ln += 1;
+			col = 1;
+		} else {
+			col += 1;
+		}
+	}
+
+	tokens.push(SyntaxToken {
+		kind,
+		span: Span {
+			start,
+			end: start + text.len(),
+			ln,
+			col,
+		},
+		text: String::from(text),
+	});
+}
+
+/// Splits source code into tokens without losing any text: whitespace, line
+/// breaks and comments are kept as trivia, and joining the text of every
+/// token gives back the original source. Chunks come from the lexer itself,
+/// with the whitespace it drops between them restored.
+pub fn lex(code: &str) -> Vec<SyntaxToken> {
+	let mut tokens: Vec<SyntaxToken> = Vec::new();
+	let mut previous: Option<Token> = None;
+	let rest = |tokens: &[SyntaxToken]| &code[tokens.last().map_or(0, |t| t.span.end)..];
+	let whitespace = |rest: &str| rest.len() - rest.trim_start_matches([' ', '\t']).len();
+
+	for chunk in lexer::chunk(String::from(code), true) {
+		let rest = rest(&tokens);
+		push_token(&mut tokens, &rest[..whitespace(rest)], SyntaxKind::Whitespace);
+
+		let kind = if chunk == "\n" {
+			previous = Some(Token::LineBreak);
+			SyntaxKind::LineBreak
+		} else if chunk.starts_with("//") || chunk.starts_with("/*") {
+			SyntaxKind::Comment
+		} else {
+			let token = lexer::token_from_chunk(chunk.clone(), previous.as_ref());
+			let kind = SyntaxKind::from_token(&token);
+			previous = Some(token);
+			kind
+		};
+		push_token(&mut tokens, &chunk, kind);
+	}
+	let rest = rest(&tokens);
+	push_token(&mut tokens, rest, SyntaxKind::Whitespace);
+
+	tokens
+}
+
+/// Converts lossless tokens into the token stream expected by the parser,
+/// identical to the output of [`lexer::tokenize`].
+pub fn to_tokens<'a>(tokens: impl Iterator<Item = &'a SyntaxToken>) -> Vec<Token> {
+	let mut result: Vec<Token> = Vec::new();
+
+	for token in tokens {
+		match token.kind {
+			SyntaxKind::Whitespace => (),
+			SyntaxKind::LineBreak => result.push(Token::LineBreak),
+			SyntaxKind::Comment => {
+				if let Some(doc) = token.text.strip_prefix("//:") {
+					result.push(Token::DocComment(String::from(doc)));
+				}
+				for _ in token.text.matches('\n') {
+					result.push(Token::LineBreak);
+				}
+			}
+			_ => {
+				let next = lexer::token_from_chunk(token.text.clone(), result.last());
+				result.push(next);
+			}
+		}
+	}
+
+	let mut result = lexer::attach_doc_comments(result, false);
+	while let Some(Token::LineBreak) = result.last() {
+		result.pop();
+	}
+	result.push(Token::EOF);
+
+	result
+}
+
+/// Joins tokens back into source code.
+pub fn to_source(tokens: &[SyntaxToken]) -> String {
+	tokens.iter().map(|t| t.text.as_str()).collect()
+}
+
+/// Builds a concrete syntax tree, grouping the contents of parentheses and
+/// braces. Unbalanced closing tokens are kept as plain tokens.
+pub fn build_tree(tokens: Vec<SyntaxToken>) -> SyntaxNode {
+	let mut stack = vec![SyntaxNode {
+		kind: SyntaxNodeKind::Root,
+		children: Vec::new(),
+	}];
+
+	for token in tokens {
+		let closes = match token.kind {
+			SyntaxKind::ArgClose => Some(SyntaxNodeKind::Arguments),
+			SyntaxKind::ScopeClose => Some(SyntaxNodeKind::Scope),
+			_ => None,
+		};
+		let opens = match token.kind {
+			SyntaxKind::ArgOpen => Some(SyntaxNodeKind::Arguments),
+			SyntaxKind::ScopeOpen => Some(SyntaxNodeKind::Scope),
+			_ => None,
+		};
+
+		if let Some(kind) = opens {
+			stack.push(SyntaxNode {
+				kind,
+				children: vec![SyntaxElement::Token(token)],
+			});
+		} else if closes.is_some() && stack.last().map(|n| n.kind) == closes {
+			let mut node = stack.pop().unwrap();
+			node.children.push(SyntaxElement::Token(token));
+			if let Some(parent) = stack.last_mut() {
+				parent.children.push(SyntaxElement::Node(node));
+			}
+		} else if let Some(node) = stack.last_mut() {
+			node.children.push(SyntaxElement::Token(token));
+		}
+	}
+
+	while stack.len() > 1 {
+		let node = stack.pop().unwrap();
+		if let Some(parent) = stack.last_mut() {
+			parent.children.push(SyntaxElement::Node(node));
+		}
+	}
+
+	stack.pop().unwrap()
+}
+
+/// Parses source code into a lossless concrete syntax tree.
+pub fn parse(code: &str) -> SyntaxNode {
+	build_tree(lex(code))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, path::Path};
+
+	use super::*;
+	use crate::lexer;
+
+	fn scripts(dir: &Path, files: &mut Vec<String>) {
+		for entry in fs::read_dir(dir).unwrap() {
+			let path = entry.unwrap().path();
+			if path.is_dir() {
+				scripts(&path, files);
+			} else if path.extension().is_some_and(|e| e == "bean") {
+				files.push(fs::read_to_string(path).unwrap());
+			}
+		}
+	}
+
+	#[test]
+	fn matches_parser_on_test_scripts() {
+		let mut files = Vec::new();
+		scripts(&Path::new(env!("CARGO_MANIFEST_DIR")).join("test"), &mut files);
+		assert!(!files.is_empty());
+
+		for code in files {
+			let tree = parse(&code);
+			assert_eq!(tree.text(), code);
+			assert_eq!(
+				format!("{:?}", tree.to_ast().unwrap()),
+				format!("{:?}", parser::parse(lexer::tokenize(code.clone())).unwrap()),
+			);
+		}
+	}
+
+	#[test]
+	fn keeps_whitespace_and_comments() {
+		let code = "a  b\t(c) /* x\ny */ \"s t\" // c\n";
+		let tokens = lex(code);
+		assert_eq!(to_source(&tokens), code);
+		let kinds: Vec<SyntaxKind> = tokens.iter().map(|t| t.kind).collect();
+		assert_eq!(
+			kinds,
+			[
+				SyntaxKind::FnName,
+				SyntaxKind::Whitespace,
+				SyntaxKind::FnName,
+				SyntaxKind::Whitespace,
+				SyntaxKind::ArgOpen,
+				SyntaxKind::FnName,
+				SyntaxKind::ArgClose,
+				SyntaxKind::Whitespace,
+				SyntaxKind::Comment,
+				SyntaxKind::Whitespace,
+				SyntaxKind::String,
+				SyntaxKind::Whitespace,
+				SyntaxKind::Comment,
+				SyntaxKind::LineBreak,
+			]
+		);
+		assert_eq!((tokens[10].span.ln, tokens[10].span.col), (2, 6));
+	}
+}
@@ -0,0 +1,514 @@
+use std::{
+	collections::HashMap,
+	fs, io,
+	path::{Path, PathBuf},
+	process,
+	sync::{
+		atomic::{AtomicU64, Ordering},
+		mpsc::{self, Receiver, RecvError, TryRecvError},
+		Mutex, PoisonError,
+	},
+	thread,
+};
+
+use bean_script::{
+	data::Data,
+	debugger::{self, Breakpoints, DebugClient, Debugger, StackFrame, Step, StopReason},
+	error::Error,
+	evaluator, lexer,
+	logger::Logger,
+	modules::{
+		registry::{ModuleRegistry, RegistryFeatures},
+		CustomModule,
+	},
+	parser,
+	scope::ScopeRef,
+	util::{make_ref, Lock, Shared, Weak},
+};
+use serde_json::{json, Value};
+
+use crate::rpc::{read_message, send};
+
+/// Connection to the client. Requests are read on a separate thread so they
+/// can be checked for while the program is running.
+struct Connection {
+	receiver: Mutex<Receiver<Value>>,
+	seq: AtomicU64,
+}
+
+impl Connection {
+	fn open() -> Self {
+		let (sender, receiver) = mpsc::channel();
+		thread::spawn(move || {
+			let mut stdin = io::stdin().lock();
+			while let Ok(Some(message)) = read_message(&mut stdin) {
+				if sender.send(message).is_err() {
+					break;
+				}
+			}
+		});
+
+		Self {
+			receiver: Mutex::new(receiver),
+			seq: AtomicU64::new(1),
+		}
+	}
+
+	fn recv(&self) -> Result<Value, RecvError> {
+		self.receiver.lock().unwrap_or_else(PoisonError::into_inner).recv()
+	}
+
+	fn try_recv(&self) -> Result<Value, TryRecvError> {
+		self.receiver.lock().unwrap_or_else(PoisonError::into_inner).try_recv()
+	}
+
+	fn next_seq(&self) -> u64 {
+		self.seq.fetch_add(1, Ordering::Relaxed)
+	}
+
+	fn respond(&self, request: &Value, body: Value) -> io::Result<()> {
+		send(json!({
+			"seq": self.next_seq(),
+			"type": "response",
+			"request_seq": request["seq"],
+			"success": true,
+			"command": request["command"],
+			"body": body,
+		}))
+	}
+
+	fn fail(&self, request: &Value, message: &str) -> io::Result<()> {
+		send(json!({
+			"seq": self.next_seq(),
+			"type": "response",
+			"request_seq": request["seq"],
+			"success": false,
+			"command": request["command"],
+			"message": message,
+		}))
+	}
+
+	fn event(&self, event: &str, body: Value) -> io::Result<()> {
+		send(json!({
+			"seq": self.next_seq(),
+			"type": "event",
+			"event": event,
+			"body": body,
+		}))
+	}
+
+	fn output(&self, category: &str, text: String) -> io::Result<()> {
+		self.event("output", json!({ "category": category, "output": text }))
+	}
+}
+
+/// Paths of the program being debugged. The main module is keyed by its
+/// directory, so breakpoints and frames in the program file are translated
+/// between the two.
+struct Program {
+	file: PathBuf,
+	dir: PathBuf,
+}
+
+impl Program {
+	fn new(path: &str) -> Self {
+		let file = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+		let mut dir = file.clone();
+		dir.pop();
+		Self { file, dir }
+	}
+
+	fn module_path(&self, source: &Path) -> PathBuf {
+		let source = fs::canonicalize(source).unwrap_or_else(|_| source.to_path_buf());
+		if source == self.file {
+			self.dir.clone()
+		} else {
+			source
+		}
+	}
+
+	fn source_path<'a>(&'a self, module: &'a Path) -> &'a Path {
+		if module == self.dir {
+			&self.file
+		} else {
+			module
+		}
+	}
+}
+
+fn set_breakpoints(
+	connection: &Connection,
+	request: &Value,
+	program: Option<&Program>,
+	breakpoints: &mut Breakpoints,
+) -> io::Result<()> {
+	let arguments = &request["arguments"];
+	let source = PathBuf::from(arguments["source"]["path"].as_str().unwrap_or_default());
+	let path = match program {
+		Some(program) => program.module_path(&source),
+		None => fs::canonicalize(&source).unwrap_or(source),
+	};
+	let lines: Vec<u64> = arguments["breakpoints"]
+		.as_array()
+		.map(|b| b.iter().filter_map(|b| b["line"].as_u64()).collect())
+		.unwrap_or_default();
+
+	breakpoints.insert(path, lines.iter().map(|ln| *ln as usize).collect());
+	connection.respond(
+		request,
+		json!({
+			"breakpoints": lines
+				.iter()
+				.map(|ln| json!({ "verified": true, "line": ln }))
+				.collect::<Vec<_>>(),
+		}),
+	)
+}
+
+fn threads(connection: &Connection, request: &Value) -> io::Result<()> {
+	connection.respond(
+		request,
+		json!({ "threads": [{ "id": 1, "name": "main" }] }),
+	)
+}
+
+fn describe(data: &Data) -> String {
+	match data {
+		Data::String(s) => format!("\"{}\"", s),
+		_ => data.to_string(),
+	}
+}
+
+struct DapClient {
+	connection: Shared<Connection>,
+	program: Shared<Program>,
+	registry: Weak<Lock<ModuleRegistry>>,
+	/// Scopes handed out as variable references during the current pause,
+	/// cleared when the program resumes.
+	references: Vec<ScopeRef>,
+}
+
+impl DapClient {
+	fn flush_output(&self) {
+		let Some(registry) = self.registry.upgrade() else {
+			return;
+		};
+		let Ok(mut registry) = registry.try_borrow_mut() else {
+			return;
+		};
+		for line in registry.logger.by_ref() {
+			let _ = self.connection.output("stdout", line + "\n");
+		}
+	}
+
+	fn reference(&mut self, scope: &ScopeRef) -> usize {
+		if let Some(i) = self.references.iter().position(|r| Shared::ptr_eq(r, scope)) {
+			return i + 1;
+		}
+		self.references.push(Shared::clone(scope));
+		self.references.len()
+	}
+
+	fn stack_trace(&self, frames: &[StackFrame]) -> Value {
+		let frames: Vec<Value> = frames
+			.iter()
+			.enumerate()
+			.rev()
+			.map(|(id, frame)| {
+				let path = self.program.source_path(&frame.file);
+				json!({
+					"id": id,
+					"name": frame.name,
+					"source": {
+						"name": path.file_name().map(|n| n.to_string_lossy()),
+						"path": path,
+					},
+					"line": frame.ln,
+					"column": 1,
+				})
+			})
+			.collect();
+		json!({ "stackFrames": frames, "totalFrames": frames.len() })
+	}
+
+	fn scopes(&mut self, frame: &StackFrame) -> Value {
+		let mut scopes = Vec::new();
+		let mut current = Some(Shared::clone(&frame.scope));
+		while let Some(scope) = current {
+			let kind = debugger::scope_kind(&scope);
+			let mut name = String::from(kind);
+			name[..1].make_ascii_uppercase();
+			scopes.push(json!({
+				"name": name,
+				"variablesReference": self.reference(&scope),
+				"expensive": false,
+			}));
+			current = scope.try_borrow().ok().and_then(|s| s.parent());
+		}
+		json!({ "scopes": scopes })
+	}
+
+	fn variables(&mut self, reference: usize) -> Value {
+		let Some(scope) = self.references.get(reference.wrapping_sub(1)).cloned() else {
+			return json!({ "variables": [] });
+		};
+		let variables: Vec<Value> = debugger::variables(&scope)
+			.into_iter()
+			.map(|(name, value)| {
+				let reference = match &value {
+					Data::Scope(scope) => self.reference(scope),
+					_ => 0,
+				};
+				json!({
+					"name": name,
+					"value": describe(&value),
+					"type": value.get_type().to_string(),
+					"variablesReference": reference,
+				})
+			})
+			.collect();
+		json!({ "variables": variables })
+	}
+
+	fn disconnect(&self, request: &Value) -> ! {
+		self.flush_output();
+		let _ = self.connection.respond(request, Value::Null);
+		process::exit(0)
+	}
+}
+
+impl DebugClient for DapClient {
+	fn poll(&mut self, breakpoints: &mut Breakpoints) -> bool {
+		self.flush_output();
+		loop {
+			let request = match self.connection.try_recv() {
+				Ok(request) => request,
+				Err(TryRecvError::Empty) => return false,
+				Err(TryRecvError::Disconnected) => process::exit(0),
+			};
+			let _ = match request["command"].as_str().unwrap_or_default() {
+				"setBreakpoints" => set_breakpoints(
+					&self.connection,
+					&request,
+					Some(&self.program),
+					breakpoints,
+				),
+				"threads" => threads(&self.connection, &request),
+				"pause" => {
+					let _ = self.connection.respond(&request, Value::Null);
+					return true;
+				}
+				"disconnect" | "terminate" => self.disconnect(&request),
+				_ => self.connection.fail(&request, "Program is running."),
+			};
+		}
+	}
+
+	fn paused(
+		&mut self,
+		reason: StopReason,
+		frames: &[StackFrame],
+		breakpoints: &mut Breakpoints,
+	) -> Step {
+		self.flush_output();
+		let reason = match reason {
+			StopReason::Entry => "entry",
+			StopReason::Breakpoint => "breakpoint",
+			StopReason::Step => "step",
+			StopReason::Pause => "pause",
+		};
+		let _ = self.connection.event(
+			"stopped",
+			json!({ "reason": reason, "threadId": 1, "allThreadsStopped": true }),
+		);
+
+		loop {
+			let Ok(request) = self.connection.recv() else {
+				process::exit(0)
+			};
+			let arguments = &request["arguments"];
+			let step = match request["command"].as_str().unwrap_or_default() {
+				"continue" => Some(Step::Continue),
+				"next" => Some(Step::Over),
+				"stepIn" => Some(Step::In),
+				"stepOut" => Some(Step::Out),
+				_ => None,
+			};
+			if let Some(step) = step {
+				self.references.clear();
+				let _ = self
+					.connection
+					.respond(&request, json!({ "allThreadsContinued": true }));
+				return step;
+			}
+
+			let _ = match request["command"].as_str().unwrap_or_default() {
+				"threads" => threads(&self.connection, &request),
+				"stackTrace" => self.connection.respond(&request, self.stack_trace(frames)),
+				"scopes" => {
+					let id = arguments["frameId"].as_u64().unwrap_or(0) as usize;
+					match frames.get(id) {
+						Some(frame) => {
+							let body = self.scopes(frame);
+							self.connection.respond(&request, body)
+						}
+						None => self.connection.fail(&request, "Unknown stack frame."),
+					}
+				}
+				"variables" => {
+					let reference = arguments["variablesReference"].as_u64().unwrap_or(0);
+					let body = self.variables(reference as usize);
+					self.connection.respond(&request, body)
+				}
+				"setBreakpoints" => set_breakpoints(
+					&self.connection,
+					&request,
+					Some(&self.program),
+					breakpoints,
+				),
+				"disconnect" | "terminate" => self.disconnect(&request),
+				_ => self.connection.respond(&request, Value::Null),
+			};
+		}
+	}
+}
+
+/// Waits for the client to configure and launch a program. Returns the path
+/// of the program, whether to stop on entry and the breakpoints set so far.
+fn configure(connection: &Connection) -> io::Result<Option<(String, bool, Breakpoints)>> {
+	let mut breakpoints = HashMap::new();
+	let mut launch = None;
+	let mut configured = false;
+
+	while !configured || launch.is_none() {
+		let Ok(request) = connection.recv() else {
+			return Ok(None);
+		};
+		let arguments = &request["arguments"];
+		match request["command"].as_str().unwrap_or_default() {
+			"initialize" => {
+				connection.respond(
+					&request,
+					json!({ "supportsConfigurationDoneRequest": true }),
+				)?;
+				connection.event("initialized", Value::Null)?;
+			}
+			"setBreakpoints" => {
+				set_breakpoints(connection, &request, None, &mut breakpoints)?
+			}
+			"launch" => match arguments["program"].as_str() {
+				Some(program) => {
+					launch = Some((
+						String::from(program),
+						arguments["stopOnEntry"].as_bool().unwrap_or(false),
+					));
+					connection.respond(&request, Value::Null)?;
+				}
+				None => connection.fail(&request, "Expected a program to launch.")?,
+			},
+			"configurationDone" => {
+				configured = true;
+				connection.respond(&request, Value::Null)?;
+			}
+			"threads" => threads(connection, &request)?,
+			"disconnect" | "terminate" => {
+				connection.respond(&request, Value::Null)?;
+				return Ok(None);
+			}
+			_ => connection.fail(&request, "Program has not been launched.")?,
+		}
+	}
+
+	let (program, stop_on_entry) = launch.unwrap();
+	Ok(Some((program, stop_on_entry, breakpoints)))
+}
+
+/// Runs a debug adapter over stdin and stdout, speaking the Debug Adapter
+/// Protocol. Output of the program is sent to the client as output events.
+pub fn run() -> io::Result<()> {
+	let connection = Shared::new(Connection::open());
+	let Some((path, stop_on_entry, breakpoints)) = configure(&connection)? else {
+		return Ok(());
+	};
+	let program = Shared::new(Program::new(&path));
+
+	let exit_code = match fs::read_to_string(&program.file) {
+		// Stdout carries the protocol, so the lexer must not warn about
+		// CRLF line endings there.
+		Ok(file) => match parser::parse(lexer::tokenize(file.replace('\r', ""))) {
+			Ok(tree) => {
+				let registry = make_ref(ModuleRegistry::new(RegistryFeatures::default()));
+				crate::add_search_paths(&mut registry.borrow_mut(), &[]);
+				let client = DapClient {
+					connection: Shared::clone(&connection),
+					program: Shared::clone(&program),
+					registry: Shared::downgrade(&registry),
+					references: Vec::new(),
+				};
+				let mut debugger = Debugger::new(Box::new(client), stop_on_entry);
+				for (source, lines) in breakpoints {
+					debugger.set_breakpoints(program.module_path(&source), lines);
+				}
+				{
+					let mut registry = registry.borrow_mut();
+					registry.logger = Logger::Backlog {
+						backlog: Default::default(),
+					};
+					registry.hooks.push(make_ref(debugger));
+				}
+
+				let program_scope = CustomModule::new(Shared::clone(&registry), program.dir.clone());
+				let result = evaluator::evaluate(&tree, make_ref(program_scope));
+				for line in registry.borrow_mut().logger.by_ref() {
+					connection.output("stdout", line + "\n")?;
+				}
+				match result {
+					Ok(_) => 0,
+					Err(error) => {
+						report(&connection, &program.file, &error)?;
+						1
+					}
+				}
+			}
+			Err(error) => {
+				report(&connection, &program.file, &error)?;
+				1
+			}
+		},
+		Err(error) => {
+			connection.output("stderr", format!("error: Failed to open file. {}\n", error))?;
+			1
+		}
+	};
+
+	connection.event("exited", json!({ "exitCode": exit_code }))?;
+	connection.event("terminated", Value::Null)?;
+
+	while let Ok(request) = connection.recv() {
+		match request["command"].as_str().unwrap_or_default() {
+			"disconnect" | "terminate" => {
+				connection.respond(&request, Value::Null)?;
+				break;
+			}
+			"setBreakpoints" => {
+				set_breakpoints(&connection, &request, Some(&program), &mut HashMap::new())?
+			}
+			"threads" => threads(&connection, &request)?,
+			_ => connection.fail(&request, "Program has exited.")?,
+		}
+	}
+
+	Ok(())
+}
+
+fn report(
+	connection: &Connection,
+	file: &Path,
+	error: &Error,
+) -> io::Result<()> {
+	let location = match error.line() {
+		Some(ln) => format!("\n-> {}:{}", file.display(), ln),
+		None => String::new(),
+	};
+	connection.output("stderr", format!("error: {}{}\n", error.message(), location))
+}
@@ -1,25 +1,39 @@
-use std::{cell::RefCell, hash::Hash, rc::Rc};
+use std::hash::Hash;
 
 use crate::{
 	error::{Error, ErrorSource},
+	modules::bean_std::collections::{List, Map},
 	pat_check,
-	scope::ScopeRef,
+	scope::{function::Function, ScopeRef},
+	user_data::UserData,
+	util::{Lock, Shared},
 };
 
+#[derive(Debug, Clone, PartialEq)]
 pub enum DataType {
 	Boolean,
 	Number,
 	String,
 	Name,
 	Scope,
+	List,
+	Map,
+	Function,
+	UserData,
 	None,
 	Or(Box<DataType>, Box<DataType>),
 	Any,
 }
 
 impl DataType {
-	pub fn from_string(string: &String) -> Result<DataType, Error> {
-		if string == "boolean" {
+	pub fn from_string(string: &str) -> Result<DataType, Error> {
+		let string = string.trim();
+		if let Some((a, b)) = string.split_once('|') {
+			Ok(DataType::Or(
+				Box::new(DataType::from_string(a)?),
+				Box::new(DataType::from_string(b)?),
+			))
+		} else if string == "boolean" {
 			Ok(DataType::Boolean)
 		} else if string == "number" {
 			Ok(DataType::Number)
@@ -29,6 +43,14 @@ impl DataType {
 			Ok(DataType::Name)
 		} else if string == "scope" {
 			Ok(DataType::Scope)
+		} else if string == "list" {
+			Ok(DataType::List)
+		} else if string == "map" {
+			Ok(DataType::Map)
+		} else if string == "function" {
+			Ok(DataType::Function)
+		} else if string == "userdata" {
+			Ok(DataType::UserData)
 		} else if string == "none" {
 			Ok(DataType::None)
 		} else if string == "any" {
@@ -48,6 +70,10 @@ impl DataType {
 			DataType::String => String::from("string"),
 			DataType::Name => String::from("name"),
 			DataType::Scope => String::from("scope"),
+			DataType::List => String::from("list"),
+			DataType::Map => String::from("map"),
+			DataType::Function => String::from("function"),
+			DataType::UserData => String::from("userdata"),
 			DataType::None => String::from("none"),
 			DataType::Any => String::from("any"),
 			DataType::Or(a, b) => a.to_string() + " | " + &b.to_string(),
@@ -61,6 +87,16 @@ impl DataType {
 			DataType::String => pat_check!(Data::String(_) = data),
 			DataType::Name => pat_check!(Data::Name { .. } = data),
 			DataType::Scope => pat_check!(Data::Scope(_) = data),
+			DataType::List => match data {
+				Data::Scope(scope) => Lock::borrow(scope).as_any().is::<List>(),
+				_ => false,
+			},
+			DataType::Map => match data {
+				Data::Scope(scope) => Lock::borrow(scope).as_any().is::<Map>(),
+				_ => false,
+			},
+			DataType::Function => pat_check!(Data::Function(_) = data),
+			DataType::UserData => pat_check!(Data::UserData(_) = data),
 			DataType::None => pat_check!(Data::None = data),
 			DataType::Or(a, b) => a.matches(data) || b.matches(data),
 			DataType::Any => true,
@@ -75,6 +111,8 @@ pub enum Data {
 	String(String),
 	Name { scope: ScopeRef, name: String },
 	Scope(ScopeRef),
+	Function(Box<Function>),
+	UserData(UserData),
 	None,
 }
 
@@ -86,6 +124,8 @@ impl Data {
 			Data::String(_) => DataType::String,
 			Data::Name { .. } => DataType::Name,
 			Data::Scope(_) => DataType::Scope,
+			Data::Function(_) => DataType::Function,
+			Data::UserData(_) => DataType::UserData,
 			Data::None => DataType::None,
 		}
 	}
@@ -104,8 +144,10 @@ impl PartialEq for Data {
 					scope: r_scope,
 					name: r_name,
 				},
-			) => Rc::ptr_eq(scope, r_scope) && name == r_name,
-			(Self::Scope(l), Self::Scope(r)) => Rc::ptr_eq(l, r),
+			) => Shared::ptr_eq(scope, r_scope) && name == r_name,
+			(Self::Scope(l), Self::Scope(r)) => Shared::ptr_eq(l, r),
+			(Self::Function(l), Self::Function(r)) => l.ptr_eq(r),
+			(Self::UserData(l), Self::UserData(r)) => l.ptr_eq(r),
 			_ => false,
 		}
 	}
@@ -138,7 +180,9 @@ impl ToString for Data {
 			Data::Number(v) => v.to_string(),
 			Data::String(s) => s.clone(),
 			Data::Name { scope: _, name } => format!("<{}>", name),
-			Data::Scope(scope) => RefCell::borrow(&scope).to_string(),
+			Data::Scope(scope) => Lock::borrow(&scope).to_string(),
+			Data::Function(_) => String::from("[function]"),
+			Data::UserData(v) => format!("[{}]", v.user_type().name()),
 			Data::None => String::from("[none]"),
 		}
 	}
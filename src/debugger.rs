@@ -0,0 +1,257 @@
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	data::Data,
+	error::Error,
+	hook::ExecutionHook,
+	modules::{
+		bean_std::collections::{List, Map},
+		CustomModule,
+	},
+	parser::PosNode,
+	scope::{
+		block_scope::BlockScope,
+		function::{CallScope, Function},
+		Scope, ScopeRef,
+	},
+	util::{MaybeSync, Shared},
+};
+
+/// Lines to pause on, keyed by the path of the module they belong to.
+pub type Breakpoints = HashMap<PathBuf, HashSet<usize>>;
+
+#[derive(Debug, Clone)]
+pub struct StackFrame {
+	pub name: String,
+	pub file: PathBuf,
+	pub ln: usize,
+	/// Innermost scope of the statement the frame is at.
+	pub scope: ScopeRef,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopReason {
+	Entry,
+	Breakpoint,
+	Step,
+	Pause,
+}
+
+/// How to continue after a pause.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+	Continue,
+	In,
+	Over,
+	Out,
+}
+
+/// Front end of a debugger, such as a debug adapter.
+pub trait DebugClient: MaybeSync {
+	/// Called before every statement while running. Returning true pauses
+	/// execution at that statement.
+	fn poll(&mut self, breakpoints: &mut Breakpoints) -> bool;
+
+	/// Called when execution pauses. Blocks until the client decides how to
+	/// continue.
+	fn paused(
+		&mut self,
+		reason: StopReason,
+		frames: &[StackFrame],
+		breakpoints: &mut Breakpoints,
+	) -> Step;
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Mode {
+	Continue,
+	In,
+	Over(usize),
+	Out(usize),
+}
+
+/// Pauses evaluation on breakpoints and steps, keeping track of the call
+/// stack. Register it as an [`ExecutionHook`] to attach it.
+pub struct Debugger {
+	client: Box<dyn DebugClient>,
+	breakpoints: Breakpoints,
+	frames: Vec<StackFrame>,
+	mode: Mode,
+	entry: bool,
+	last_statement: Option<(usize, usize, usize)>,
+}
+
+impl Debugger {
+	pub fn new(client: Box<dyn DebugClient>, stop_on_entry: bool) -> Self {
+		Self {
+			client,
+			breakpoints: HashMap::new(),
+			frames: Vec::new(),
+			mode: if stop_on_entry { Mode::In } else { Mode::Continue },
+			entry: stop_on_entry,
+			last_statement: None,
+		}
+	}
+
+	pub fn set_breakpoints(&mut self, file: PathBuf, lines: HashSet<usize>) {
+		self.breakpoints.insert(file, lines);
+	}
+
+	pub fn frames(&self) -> &[StackFrame] {
+		&self.frames
+	}
+
+	/// Runs before each statement, pausing when a breakpoint or step says so.
+	fn statement(&mut self, file: &Path, pos_node: &PosNode, scope: &ScopeRef) {
+		if self.frames.is_empty() {
+			self.frames.push(StackFrame {
+				name: String::from("[main]"),
+				file: file.to_path_buf(),
+				ln: 0,
+				scope: Shared::clone(scope),
+			});
+		}
+
+		let depth = self.frames.len();
+		let ln = pos_node.start_ln;
+		let frame = self.frames.last_mut().unwrap();
+		frame.file = file.to_path_buf();
+		frame.ln = ln;
+		frame.scope = Shared::clone(scope);
+
+		let statement = (depth, ln, Shared::as_ptr(scope) as *const () as usize);
+		let repeated = self.last_statement == Some(statement);
+		self.last_statement = Some(statement);
+
+		let reason = if self.client.poll(&mut self.breakpoints) {
+			Some(StopReason::Pause)
+		} else if repeated {
+			None
+		} else if self.entry {
+			Some(StopReason::Entry)
+		} else if match self.mode {
+			Mode::Continue => false,
+			Mode::In => true,
+			Mode::Over(d) => depth <= d,
+			Mode::Out(d) => depth < d,
+		} {
+			Some(StopReason::Step)
+		} else if self
+			.breakpoints
+			.get(&self.frames[depth - 1].file)
+			.is_some_and(|lines| lines.contains(&ln))
+		{
+			Some(StopReason::Breakpoint)
+		} else {
+			None
+		};
+
+		if let Some(reason) = reason {
+			self.entry = false;
+			let step = self.client.paused(reason, &self.frames, &mut self.breakpoints);
+			self.mode = match step {
+				Step::Continue => Mode::Continue,
+				Step::In => Mode::In,
+				Step::Over => Mode::Over(depth),
+				Step::Out => Mode::Out(depth),
+			};
+		}
+	}
+}
+
+impl ExecutionHook for Debugger {
+	fn node_enter(&mut self, file: &Path, node: &PosNode, scope: &ScopeRef, statement: bool) {
+		if statement {
+			self.statement(file, node, scope);
+		}
+	}
+
+	fn call(&mut self, name: &str, function: &Function, _args: &[Data], scope: &ScopeRef) {
+		if let Function::Custom { .. } = function {
+			let caller = self.frames.last();
+			self.frames.push(StackFrame {
+				name: String::from(name),
+				file: caller.map_or_else(PathBuf::new, |f| f.file.clone()),
+				ln: caller.map_or(0, |f| f.ln),
+				scope: Shared::clone(scope),
+			});
+		}
+	}
+
+	fn call_return(&mut self, _name: &str, function: &Function, _result: &Result<Data, Error>) {
+		if let Function::Custom { .. } = function {
+			self.frames.pop();
+		}
+	}
+}
+
+/// Describes the kind of a scope for display, like `block` or `call`.
+pub fn scope_kind(scope: &ScopeRef) -> &'static str {
+	let Ok(scope) = scope.try_borrow() else {
+		return "scope";
+	};
+	let any = scope.as_any();
+	if any.is::<BlockScope>() {
+		"block"
+	} else if any.is::<CallScope>() {
+		"call"
+	} else if any.is::<CustomModule>() {
+		"module"
+	} else if any.is::<List>() {
+		"list"
+	} else if any.is::<Map>() {
+		"map"
+	} else {
+		"scope"
+	}
+}
+
+/// Lists the values visible directly in a scope: local variables and
+/// constants, functions, call arguments and collection items.
+pub fn variables(scope: &ScopeRef) -> Vec<(String, Data)> {
+	let Ok(scope) = scope.try_borrow() else {
+		return Vec::new();
+	};
+	let any = scope.as_any();
+	let mut variables = Vec::new();
+
+	let functions = if let Some(block) = any.downcast_ref::<BlockScope>() {
+		block.get_function_list()
+	} else if let Some(call) = any.downcast_ref::<CallScope>() {
+		for (i, arg) in call.args().iter().enumerate() {
+			variables.push((format!("p({})", i), arg.clone()));
+		}
+		call.local_functions().clone()
+	} else if let Some(module) = any.downcast_ref::<CustomModule>() {
+		module.local_functions()
+	} else if let Some(list) = any.downcast_ref::<List>() {
+		for (i, item) in list.items.iter().enumerate() {
+			variables.push((i.to_string(), item.clone()));
+		}
+		HashMap::new()
+	} else if let Some(map) = any.downcast_ref::<Map>() {
+		for (key, value) in map.hash.iter() {
+			variables.push((key.to_string(), value.clone()));
+		}
+		HashMap::new()
+	} else {
+		HashMap::new()
+	};
+
+	let mut functions: Vec<(String, Function)> = functions.into_iter().collect();
+	functions.sort_by(|a, b| a.0.cmp(&b.0));
+	for (name, function) in functions {
+		match function {
+			Function::Variable { value, .. } | Function::Constant { value } => {
+				variables.push((name, value))
+			}
+			Function::Custom { .. } => variables.push((name, Data::Function(Box::new(function)))),
+			Function::BuiltIn { .. } => (),
+		}
+	}
+
+	variables
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+
+use crate::{
+	checker::{name_literal, string_literal},
+	parser::{Node, PosNode},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocFormat {
+	Markdown,
+	Html,
+}
+
+/// Documentation for one exported name of a module.
+#[derive(Debug, Clone)]
+pub struct DocItem {
+	pub name: String,
+	pub usage: String,
+	pub description: Vec<String>,
+	pub ln: usize,
+}
+
+struct Definition<'a> {
+	annotated_name: &'a str,
+	parameters: &'a [Box<PosNode>],
+	doc: Option<&'a String>,
+	ln: usize,
+}
+
+fn describe_parameter(parameter: &PosNode) -> Option<String> {
+	if let Some(name) = name_literal(parameter) {
+		return Some(name.trim().to_string());
+	}
+	let Node::ParameterBlock { body } = &parameter.node else {
+		return None;
+	};
+	let Some(Node::FnCall {
+		name, parameters, ..
+	}) = body.first().map(|n| &n.node)
+	else {
+		return None;
+	};
+	let param_name = parameters.first().and_then(|p| name_literal(p))?.trim();
+
+	match name.as_str() {
+		"opt" => Some(format!("{}?", param_name)),
+		"rest" => Some(format!("...{}", param_name)),
+		_ => None,
+	}
+}
+
+fn usage(name: &str, definition: &Definition) -> String {
+	let return_type = definition
+		.annotated_name
+		.split_once(':')
+		.map(|(_, t)| t.trim());
+	let parameters: Vec<String> = definition
+		.parameters
+		.iter()
+		.skip(1)
+		.filter_map(|p| describe_parameter(p))
+		.collect();
+
+	let mut usage = String::from(name);
+	if !parameters.is_empty() {
+		usage += &format!("({})", parameters.join(", "));
+	}
+	if let Some(return_type) = return_type {
+		usage += ": ";
+		usage += return_type;
+	}
+	usage
+}
+
+fn describe(name: &str, exported: &str, definition: &Definition) -> DocItem {
+	let mut description: Vec<String> = definition
+		.doc
+		.map(|doc| {
+			doc.split('\n')
+				.map(|line| line.strip_prefix(' ').unwrap_or(line).trim_end().to_string())
+				.collect()
+		})
+		.unwrap_or_default();
+	let usage = match description.first() {
+		Some(line) if line.starts_with(name) => {
+			let line = line.clone();
+			description.remove(0);
+			line.replacen(name, exported, 1)
+		}
+		_ => usage(exported, definition),
+	};
+
+	DocItem {
+		name: String::from(exported),
+		usage,
+		description,
+		ln: definition.ln,
+	}
+}
+
+fn definition(pos_node: &PosNode) -> Option<(&str, Definition<'_>)> {
+	let Node::FnCall {
+		name,
+		parameters,
+		doc,
+		..
+	} = &pos_node.node
+	else {
+		return None;
+	};
+	if !matches!(name.as_str(), "fn" | "let" | "const") {
+		return None;
+	}
+	let target = parameters.first().and_then(|p| name_literal(p))?;
+
+	Some((
+		target.split(':').next().unwrap_or_default().trim(),
+		Definition {
+			annotated_name: target,
+			parameters,
+			doc: doc.as_ref(),
+			ln: pos_node.ln,
+		},
+	))
+}
+
+fn children(pos_node: &PosNode) -> Vec<&PosNode> {
+	match &pos_node.node {
+		Node::FnCall {
+			parameters,
+			body_fn,
+			..
+		} => parameters
+			.iter()
+			.chain(body_fn.iter())
+			.map(|n| n.as_ref())
+			.collect(),
+		Node::Scope { body } | Node::ParameterBlock { body } | Node::Program { body } => {
+			body.iter().map(|n| n.as_ref()).collect()
+		}
+		Node::FnAccess { target, call } => vec![target.as_ref(), call.as_ref()],
+		_ => Vec::new(),
+	}
+}
+
+/// Finds the documentation of the last `fn`, `let` or `const` definition of
+/// `name` anywhere in the tree, whether or not it is exported.
+pub fn find(tree: &PosNode, name: &str) -> Option<DocItem> {
+	let mut found = None;
+	let mut stack = vec![tree];
+
+	while let Some(pos_node) = stack.pop() {
+		if let Some((target_name, definition)) = definition(pos_node) {
+			if target_name == name
+				&& found.as_ref().is_none_or(|item: &DocItem| item.ln < definition.ln)
+			{
+				found = Some(describe(name, name, &definition));
+			}
+		}
+		stack.extend(children(pos_node));
+	}
+
+	found
+}
+
+/// Collects the documentation of every name exported by a module, in the
+/// order they are exported. A first doc line that starts with the exported
+/// name is used as its usage; otherwise the usage is built from the declared
+/// parameters.
+pub fn collect(tree: &PosNode) -> Vec<DocItem> {
+	let Node::Program { body } = &tree.node else {
+		return Vec::new();
+	};
+
+	let mut definitions: HashMap<String, Definition> = HashMap::new();
+	let mut items = Vec::new();
+
+	for statement in body {
+		if let Some((target_name, definition)) = definition(statement) {
+			definitions.insert(String::from(target_name), definition);
+			continue;
+		}
+		let Node::FnCall {
+			name, parameters, ..
+		} = &statement.node
+		else {
+			continue;
+		};
+		let Some(target) = parameters.first().and_then(|p| name_literal(p)) else {
+			continue;
+		};
+		let target_name = target.split(':').next().unwrap_or_default().trim();
+
+		if name == "export" {
+			let exported = parameters
+				.get(1)
+				.and_then(|p| string_literal(p))
+				.map_or(target_name, |alias| alias.as_str());
+			if let Some(definition) = definitions.get(target_name) {
+				items.push(describe(target_name, exported, definition));
+			}
+		}
+	}
+
+	items
+}
+
+fn escape_html(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+/// Renders the documentation of a module.
+pub fn render(module_name: &str, items: &[DocItem], format: DocFormat) -> String {
+	match format {
+		DocFormat::Markdown => {
+			let mut string = format!("# {}\n", module_name);
+			for item in items {
+				string += &format!("\n## `{}`\n", item.usage);
+				if !item.description.is_empty() {
+					string += &format!("\n{}\n", item.description.join("\n"));
+				}
+			}
+			string
+		}
+		DocFormat::Html => {
+			let mut string = format!(
+				"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+				escape_html(module_name)
+			);
+			for item in items {
+				string += &format!(
+					"<section id=\"{}\">\n<h2><code>{}</code></h2>\n",
+					escape_html(&item.name),
+					escape_html(&item.usage)
+				);
+				if !item.description.is_empty() {
+					string += &format!(
+						"<p>{}</p>\n",
+						item.description
+							.iter()
+							.map(|line| escape_html(line))
+							.collect::<Vec<String>>()
+							.join("<br>\n")
+					);
+				}
+				string += "</section>\n";
+			}
+			string + "</body>\n</html>\n"
+		}
+	}
+}
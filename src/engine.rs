@@ -0,0 +1,223 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use crate::{
+	compiled::CompiledScript,
+	convert::{FromData, IntoArgs},
+	data::Data,
+	error::{BeanResult, Error, ErrorSource},
+	evaluator, lexer,
+	logger::Logger,
+	modules::{
+		registry::{ModuleRegistry, RegistryFeatures},
+		CustomModule, ModuleBuilder,
+	},
+	parser,
+	scope::{function::Function, Scope, ScopeRef},
+	util::{make_ref, MutRc, Shared},
+};
+
+/// Runs bean code from Rust. An engine owns a registry and a main module;
+/// globals defined by one evaluation stay available to the next.
+pub struct Engine {
+	registry: MutRc<ModuleRegistry>,
+	module: MutRc<CustomModule>,
+}
+
+impl Engine {
+	pub fn new() -> Self {
+		Self::with_features(RegistryFeatures::default())
+	}
+
+	pub fn with_features(features: RegistryFeatures) -> Self {
+		let registry = make_ref(ModuleRegistry::new(features));
+		let module = make_ref(CustomModule::new(Shared::clone(&registry), PathBuf::new()));
+		Self { registry, module }
+	}
+
+	/// The registry scripts are evaluated with, for registering modules and
+	/// hooks.
+	pub fn registry(&self) -> MutRc<ModuleRegistry> {
+		Shared::clone(&self.registry)
+	}
+
+	/// The main module, which holds the globals.
+	pub fn module(&self) -> MutRc<CustomModule> {
+		Shared::clone(&self.module)
+	}
+
+	fn scope(&self) -> ScopeRef {
+		Shared::clone(&self.module) as ScopeRef
+	}
+
+	/// Makes a builtin module available to `use` under `name`.
+	pub fn register_module(&self, name: &str, constructor: fn(&mut ModuleBuilder)) {
+		self.registry
+			.borrow_mut()
+			.register_builtin(String::from(name), constructor);
+	}
+
+	pub fn set_logger(&self, logger: Logger) {
+		self.registry.borrow_mut().set_logger(logger);
+	}
+
+	/// Keeps printed lines instead of writing them to stdout, until they are
+	/// read with [`Engine::take_output`].
+	pub fn capture_output(&self) {
+		self.set_logger(Logger::Backlog {
+			backlog: Default::default(),
+		});
+	}
+
+	/// Lines printed since the last call, when output is captured.
+	pub fn take_output(&self) -> Vec<String> {
+		self.registry.borrow_mut().logger.by_ref().collect()
+	}
+
+	/// Evaluates code in the main module, returning the value of its last
+	/// statement. Relative imports are resolved from the directory of the
+	/// last evaluated file, or the current directory.
+	pub fn eval_str(&self, code: &str) -> Result<Data, Error> {
+		let tree = parser::parse(lexer::tokenize(String::from(code)))?;
+		evaluator::evaluate(&tree, self.scope())
+	}
+
+	/// Evaluates a file in the main module, returning the value of its last
+	/// statement. Relative imports are resolved from the directory of the
+	/// file.
+	pub fn eval_file(&self, path: impl AsRef<Path>) -> Result<Data, Error> {
+		let path = path.as_ref();
+		let source = ErrorSource::File(path.to_string_lossy().to_string());
+		let code = fs::read_to_string(path).map_err(|e| {
+			Error::new(
+				&format!("Error reading file {}: {}", path.display(), e),
+				ErrorSource::Internal,
+			)
+		})?;
+		let tree = match parser::parse(lexer::tokenize(code)) {
+			Ok(tree) => tree,
+			Err(error) => return Err(error.trace(source)),
+		};
+
+		let mut dir = path.to_path_buf();
+		dir.pop();
+		self.module.borrow_mut().file_path = dir;
+
+		evaluator::evaluate(&tree, self.scope()).trace(source)
+	}
+
+	/// Evaluates a compiled script in the main module, returning the value of
+	/// its last statement. Scripts compiled from files resolve relative
+	/// imports from the directory of the file.
+	pub fn run(&self, script: &CompiledScript) -> Result<Data, Error> {
+		if let Some(path) = script.path() {
+			let mut dir = path.to_path_buf();
+			dir.pop();
+			self.module.borrow_mut().file_path = dir;
+		}
+		script.run(self.scope())
+	}
+
+	/// Calls a global or builtin function by name, converting its arguments
+	/// and result. Arguments are a list of values or a tuple of Rust values.
+	pub fn call_function<R: FromData>(&self, name: &str, args: impl IntoArgs) -> Result<R, Error> {
+		let function = self.module.borrow().get_function(name).ok_or_else(|| {
+			Error::new(
+				&format!("Unknown value or function {}.", name),
+				ErrorSource::Internal,
+			)
+		})?;
+		self.call(&function, args)
+	}
+
+	/// Calls a function value, such as a callback passed from a script.
+	pub fn call<R: FromData>(&self, function: &Function, args: impl IntoArgs) -> Result<R, Error> {
+		R::from_data(function.call(args.into_args(), None, self.scope())?)
+	}
+
+	/// The value of a global variable or constant, or a global function as a
+	/// function value.
+	pub fn get_global(&self, name: &str) -> Option<Data> {
+		match self.module.borrow().local_function(name)? {
+			Function::Variable { value, .. } | Function::Constant { value } => Some(value),
+			function => Some(Data::Function(Box::new(function))),
+		}
+	}
+
+	/// Defines or replaces a global variable, like `let` does.
+	pub fn set_global(&self, name: &str, value: Data) {
+		let function = Function::Variable {
+			value,
+			scope_ref: self.scope(),
+			name: String::from(name),
+		};
+		self.module.borrow_mut().set_function(name, function);
+	}
+}
+
+impl Default for Engine {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// With the `sync` feature, engines and values can be sent to and shared
+// between threads.
+#[cfg(feature = "sync")]
+const _: fn() = || {
+	fn assert_sync<T: Send + Sync>() {}
+	assert_sync::<Engine>();
+	assert_sync::<Data>();
+	assert_sync::<Shared<ModuleRegistry>>();
+};
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn eval_str_returns_last_value() {
+		let engine = Engine::new();
+		assert_eq!(engine.eval_str("let(<x>): 2\n*(x, 21)").unwrap(), Data::Number(42.0));
+		// Globals stay defined for the next evaluation.
+		assert_eq!(engine.eval_str("x").unwrap(), Data::Number(2.0));
+		assert!(engine.eval_str("missing").is_err());
+	}
+
+	#[test]
+	fn call_function_converts_arguments_and_result() {
+		let engine = Engine::new();
+		engine
+			.eval_str("fn(<greet>, <name: string>, <times: number>): +(name, \" \", str(times))")
+			.unwrap();
+		let greeting: String = engine.call_function("greet", ("bean", 3)).unwrap();
+		assert_eq!(greeting, "bean 3");
+
+		assert!(engine.call_function::<String>("greet", (1, 2)).is_err());
+		assert!(engine.call_function::<f64>("greet", ("bean", 3)).is_err());
+		assert!(engine.call_function::<()>("missing", ()).is_err());
+	}
+
+	#[test]
+	fn globals() {
+		let engine = Engine::new();
+		engine.set_global("limit", Data::Number(10.0));
+		assert_eq!(engine.eval_str("+(limit, 1)").unwrap(), Data::Number(11.0));
+
+		engine.eval_str("limit: 5\nconst(<name>): \"bean\"").unwrap();
+		assert_eq!(engine.get_global("limit"), Some(Data::Number(5.0)));
+		assert_eq!(engine.get_global("name"), Some(Data::String(String::from("bean"))));
+		assert_eq!(engine.get_global("missing"), None);
+	}
+
+	#[test]
+	fn captures_output() {
+		let engine = Engine::new();
+		engine.capture_output();
+		engine.eval_str("print(\"a\", 1)\nprint(\"b\")").unwrap();
+		assert_eq!(engine.take_output(), ["a 1", "b"]);
+		assert!(engine.take_output().is_empty());
+	}
+}
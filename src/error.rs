@@ -22,6 +22,18 @@ impl Error {
 		}
 	}
 
+	pub fn message(&self) -> &str {
+		&self.msg
+	}
+
+	/// The innermost line the error was traced to, if any.
+	pub fn line(&self) -> Option<usize> {
+		self.trace.iter().find_map(|source| match source {
+			ErrorSource::Line(ln) => Some(*ln),
+			_ => None,
+		})
+	}
+
 	pub fn get_source(&self) -> String {
 		let mut ln = None;
 		let mut file = None;
@@ -43,7 +55,11 @@ impl Error {
 				break;
 			}
 		}
-		format!("{}:{}", file.unwrap_or(&String::from("input")), ln.unwrap())
+		let file = file.map_or("input", |f| f.as_str());
+		match ln {
+			Some(ln) => format!("{}:{}", file, ln),
+			None => String::from(file),
+		}
 	}
 }
 
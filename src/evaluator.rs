@@ -1,25 +1,55 @@
-use std::{borrow::Borrow, cell::RefCell, rc::Rc};
+use std::borrow::Borrow;
 
 use crate::{
 	data::Data,
 	error::{BeanResult, Error, ErrorSource},
+	hook::Attached,
 	parser::{Node, PosNode},
 	scope::{block_scope::BlockScope, function::Function, ScopeRef},
+	user_data,
+	util::{Lock, Shared},
 };
 
-pub fn evaluate_verbose(
+/// Evaluates a node, telling `hooks` about it.
+fn observe(
 	pos_node: &PosNode,
 	scope_ref: ScopeRef,
 	return_scope: bool,
 	access_scope_ref: Option<ScopeRef>,
+	statement: bool,
+	hooks: Option<&Attached>,
+) -> Result<Data, Error> {
+	let Some(hooks) = hooks else {
+		return evaluate_node(pos_node, scope_ref, return_scope, access_scope_ref, None);
+	};
+
+	hooks.node_enter(pos_node, &scope_ref, statement);
+	let result = evaluate_node(
+		pos_node,
+		scope_ref,
+		return_scope,
+		access_scope_ref,
+		Some(hooks),
+	);
+	hooks.node_exit(pos_node, &result);
+	result
+}
+
+fn evaluate_node(
+	pos_node: &PosNode,
+	scope_ref: ScopeRef,
+	return_scope: bool,
+	access_scope_ref: Option<ScopeRef>,
+	hooks: Option<&Attached>,
 ) -> Result<Data, Error> {
 	match &pos_node.node {
 		Node::FnCall {
 			name,
 			parameters,
 			body_fn,
+			..
 		} => {
-			let scope = RefCell::borrow(&scope_ref);
+			let scope = Lock::borrow(&scope_ref);
 			let function = scope.get_function(&name).ok_or_else(|| {
 				Error::new(
 					&format!("Unknown value or function {}.", name),
@@ -30,44 +60,56 @@ pub fn evaluate_verbose(
 
 			let mut args: Vec<Data> = Vec::new();
 			for n in parameters {
-				args.push(evaluate(
+				args.push(observe(
 					n,
-					Rc::clone(access_scope_ref.as_ref().unwrap_or(&scope_ref)),
+					Shared::clone(access_scope_ref.as_ref().unwrap_or(&scope_ref)),
+					false,
+					None,
+					false,
+					hooks,
 				)?);
 			}
 
+			if let Some(hooks) = hooks {
+				hooks.call(name, &function, &args, &scope_ref);
+			}
 			let return_value = function
 				.call_from(
 					args,
 					if let Some(body) = body_fn {
 						Some(Function::Custom {
-							body: Rc::new(*body.clone()),
-							scope_ref: Rc::clone(
+							body: Shared::new(*body.clone()),
+							scope_ref: Shared::clone(
 								access_scope_ref.as_ref().unwrap_or(&scope_ref),
 							),
+							signature: None,
+							return_type: None,
 						})
 					} else {
 						None
 					},
-					Rc::clone(&scope_ref),
+					Shared::clone(&scope_ref),
 					access_scope_ref,
 				)
 				.trace(ErrorSource::Line(pos_node.ln));
+			if let Some(hooks) = hooks {
+				hooks.call_return(name, &function, &return_value);
+			}
 
 			return return_value;
 		}
 		Node::Scope { body } => {
-			let scope = BlockScope::new(Some(Rc::clone(&scope_ref)));
-			let scope_ref = Rc::new(RefCell::new(scope));
+			let scope = BlockScope::new(Some(Shared::clone(&scope_ref)));
+			let scope_ref = Shared::new(Lock::new(scope));
 
 			for n in body {
-				evaluate(n, Rc::clone(&scope_ref) as ScopeRef)?;
-				if RefCell::borrow(&scope_ref).did_break() {
+				observe(n, Shared::clone(&scope_ref) as ScopeRef, false, None, true, hooks)?;
+				if Lock::borrow(&scope_ref).did_break() {
 					break;
 				}
 			}
 
-			let scope: &RefCell<BlockScope> = scope_ref.borrow();
+			let scope: &Lock<BlockScope> = scope_ref.borrow();
 			let return_value = scope.borrow().return_value.clone();
 			return if return_scope {
 				Ok(Data::Scope(scope_ref))
@@ -78,26 +120,37 @@ pub fn evaluate_verbose(
 		Node::ParameterBlock { body } => {
 			let mut return_value: Data = Data::None;
 			for n in body {
-				return_value = evaluate(n, Rc::clone(&scope_ref))?;
+				if let Node::Comment(_) | Node::EmptyLine = n.node {
+					continue;
+				}
+				return_value = observe(n, Shared::clone(&scope_ref), false, None, false, hooks)?;
 			}
 
 			return Ok(return_value);
 		}
 		Node::Program { body } => {
+			let mut return_value = Data::None;
 			for n in body {
-				evaluate(n, Rc::clone(&scope_ref))?;
+				return_value = observe(n, Shared::clone(&scope_ref), false, None, true, hooks)?;
 			}
-			return Ok(Data::None);
+			return Ok(return_value);
 		}
 		Node::FnAccess { target, call } => {
-			let target = evaluate(target, Rc::clone(&scope_ref))?;
+			let target = observe(target, Shared::clone(&scope_ref), false, None, false, hooks)?;
 
-			if let Data::Scope(target_scope) = target {
-				evaluate_verbose(
-					&call,
-					Rc::clone(&target_scope),
+			let target_scope = match &target {
+				Data::Scope(target_scope) => Some(Shared::clone(target_scope)),
+				Data::UserData(this) => Some(user_data::method_scope(this.clone())),
+				_ => None,
+			};
+			if let Some(target_scope) = target_scope {
+				observe(
+					call,
+					target_scope,
 					false,
-					Some(Rc::clone(access_scope_ref.as_ref().unwrap_or(&scope_ref))),
+					Some(Shared::clone(access_scope_ref.as_ref().unwrap_or(&scope_ref))),
+					false,
+					hooks,
 				)
 			} else {
 				return Err(Error::new(
@@ -113,13 +166,36 @@ pub fn evaluate_verbose(
 		Node::Number(v) => Ok(Data::Number(*v)),
 		Node::String(v) => Ok(Data::String(v.clone())),
 		Node::Name(name) => Ok(Data::Name {
-			scope: Rc::clone(&scope_ref),
+			scope: Shared::clone(&scope_ref),
 			name: name.clone(),
 		}),
 		Node::None => Ok(Data::None),
+		Node::Comment(_) | Node::EmptyLine => Ok(Data::None),
 	}
 }
 
+/// Evaluates a node and everything inside it. The hooks of the registry the
+/// scope belongs to are looked up once here, since nested nodes belong to
+/// the same module; function bodies look them up again when called.
+pub fn evaluate_verbose(
+	pos_node: &PosNode,
+	scope_ref: ScopeRef,
+	return_scope: bool,
+	access_scope_ref: Option<ScopeRef>,
+) -> Result<Data, Error> {
+	// Names are looked up in the target of the dot operator, but the node
+	// itself belongs to the scope it was written in.
+	let hooks = Attached::find(access_scope_ref.as_ref().unwrap_or(&scope_ref));
+	observe(
+		pos_node,
+		scope_ref,
+		return_scope,
+		access_scope_ref,
+		false,
+		hooks.as_ref(),
+	)
+}
+
 pub fn evaluate(node: &PosNode, scope_ref: ScopeRef) -> Result<Data, Error> {
 	evaluate_verbose(node, scope_ref, false, None)
 }
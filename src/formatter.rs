@@ -0,0 +1,439 @@
+use crate::{
+	error::Error,
+	lexer,
+	parser::{self, Node, PosNode},
+};
+
+const MAX_WIDTH: usize = 100;
+const TAB_WIDTH: usize = 4;
+
+/// Pretty-prints bean source code in the canonical style. Comments and
+/// single blank lines between statements are preserved. Formatting is
+/// idempotent: formatting already formatted code returns it unchanged.
+///
+/// The parser does not distinguish a call with empty parentheses from a
+/// bare name, so calls without arguments are written without them: `x()`
+/// becomes `x`, which calls `x` the same way.
+pub fn format(code: String) -> Result<String, Error> {
+	let tree = parser::parse(lexer::tokenize_with_trivia(code))?;
+	let body = match &tree.node {
+		Node::Program { body } => format_body(body, 0),
+		_ => format_node(&tree, 0),
+	};
+
+	if body.is_empty() {
+		Ok(body)
+	} else {
+		Ok(body + "\n")
+	}
+}
+
+fn indent_str(indent: usize) -> String {
+	"\t".repeat(indent)
+}
+
+fn is_trivia(pos_node: &PosNode) -> bool {
+	matches!(pos_node.node, Node::Comment(_) | Node::EmptyLine)
+}
+
+/// Line on which a node starts, for nodes that can't span lines on their
+/// own. Block comments may contain line breaks, which the parser counts
+/// before assigning the node its line.
+fn start_ln(pos_node: &PosNode) -> usize {
+	match &pos_node.node {
+		Node::Comment(text) => pos_node.ln - text.matches('\n').count(),
+		_ => pos_node.ln,
+	}
+}
+
+/// The comment a call's body starts with. Trivia is parsed like any other
+/// node, so in `let(<x>): /* c */ 5` the comment takes the place of the
+/// body and `5` follows as the next statement.
+fn comment_body(pos_node: &PosNode) -> Option<&str> {
+	match &pos_node.node {
+		Node::FnCall {
+			body_fn: Some(body),
+			..
+		} => match &body.node {
+			Node::Comment(text) => Some(text.trim_end()),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+fn format_body(body: &[Box<PosNode>], indent: usize) -> String {
+	let mut lines: Vec<String> = Vec::new();
+	let mut previous: Option<&PosNode> = None;
+	// Whether the last call is still waiting for its body, and whether its
+	// line ends in a line comment.
+	let mut dangling = false;
+	let mut closed = false;
+	// Whether an `else` goes on the same line, after an `if` with a body.
+	let mut chains_else = false;
+
+	for statement in body {
+		let is_body = dangling;
+		match &statement.node {
+			Node::EmptyLine if dangling => continue,
+			Node::EmptyLine => {
+				if lines.last().is_some_and(|l| !l.is_empty()) {
+					lines.push(String::new());
+				}
+				previous = None;
+				chains_else = false;
+				continue;
+			}
+			Node::Comment(text) => {
+				let trailing =
+					previous.is_some_and(|p| !is_trivia(p) && p.ln == start_ln(statement));
+				let comment = text.trim_end();
+				match lines.last_mut() {
+					Some(line) if trailing && !closed => {
+						line.push(' ');
+						line.push_str(comment);
+					}
+					_ => lines.push(indent_str(indent) + comment),
+				}
+				closed = comment.starts_with("//");
+			}
+			_ if dangling && closed => {
+				lines.push(indent_str(indent + 1) + &format_node(statement, indent + 1));
+			}
+			_ if dangling => {
+				let formatted = format_node(statement, indent);
+				if let Some(line) = lines.last_mut() {
+					line.push(' ');
+					line.push_str(&formatted);
+				}
+			}
+			Node::FnCall { name, .. } if chains_else && (name == "else" || name == "else_if") => {
+				let formatted = format_node(statement, indent);
+				if let Some(line) = lines.last_mut() {
+					line.push(' ');
+					line.push_str(&formatted);
+				}
+			}
+			_ => lines.push(indent_str(indent) + &format_node(statement, indent)),
+		}
+
+		if is_trivia(statement) {
+			chains_else &= dangling;
+		} else {
+			if !is_body {
+				chains_else = matches!(&statement.node, Node::FnCall { name, body_fn: Some(_), .. } if name == "if" || name == "else_if");
+			}
+			let comment = comment_body(statement);
+			dangling = comment.is_some();
+			closed = comment.is_some_and(|c| c.starts_with("//"));
+		}
+		previous = Some(statement);
+	}
+
+	while lines.last().is_some_and(|l| l.is_empty()) {
+		lines.pop();
+	}
+
+	lines.join("\n")
+}
+
+/// A line of formatted code. Nothing can follow a line comment on the same
+/// line, so a comma after the code has to go before any comments.
+struct Line {
+	text: String,
+	code_end: usize,
+	closed: bool,
+}
+
+impl Line {
+	fn new() -> Self {
+		Self {
+			text: String::new(),
+			code_end: 0,
+			closed: false,
+		}
+	}
+
+	/// Adds code or a comment after the text on the line. Block comments
+	/// right after code stay before the comma.
+	fn push(&mut self, text: &str, code: bool) {
+		let follows_code = !self.closed && self.code_end == self.text.len() && self.code_end > 0;
+		if !self.text.is_empty() {
+			self.text.push(' ');
+		}
+		self.text.push_str(text);
+		if code || follows_code && text.starts_with("/*") {
+			self.code_end = self.text.len();
+		}
+		self.closed = text.starts_with("//");
+	}
+
+	fn with_comma(&self) -> String {
+		let (code, comments) = self.text.split_at(self.code_end);
+		format!("{},{}", code, comments)
+	}
+}
+
+/// Lines of the nodes in a parameter, keeping comments where they were
+/// written. Comments at the start that are on `previous_ln`, the line the
+/// previous parameter ended on, are returned separately so they can stay
+/// after it.
+fn format_items(
+	items: &[Box<PosNode>],
+	indent: usize,
+	previous_ln: Option<usize>,
+) -> (Vec<String>, Vec<Line>) {
+	let mut trailing = Vec::new();
+	let mut lines: Vec<Line> = Vec::new();
+	let mut last_ln = previous_ln;
+
+	for item in items {
+		let (text, code, same_line) = match &item.node {
+			Node::EmptyLine => continue,
+			Node::Comment(text) => {
+				let same_line = last_ln == Some(start_ln(item));
+				if lines.is_empty() && same_line {
+					trailing.push(text.trim_end().to_string());
+					last_ln = Some(item.ln);
+					continue;
+				}
+				(text.trim_end().to_string(), false, same_line)
+			}
+			_ => {
+				// Nodes in one parameter are joined, unless a comment on
+				// its own line comes between them.
+				let after_code = lines.last().is_some_and(|line| line.code_end > 0);
+				(
+					format_node(item, indent),
+					true,
+					after_code || last_ln == Some(item.start_ln),
+				)
+			}
+		};
+
+		match lines.last_mut() {
+			Some(line) if same_line && !line.closed => line.push(&text, code),
+			_ => {
+				let mut line = Line::new();
+				line.push(&text, code);
+				lines.push(line);
+			}
+		}
+		last_ln = Some(item.ln);
+	}
+
+	(trailing, lines)
+}
+
+fn format_parameters(parameters: &[Box<PosNode>], indent: usize) -> String {
+	let format = |indent: usize| {
+		let mut previous_ln = None;
+		parameters
+			.iter()
+			.map(|parameter| {
+				let items = match &parameter.node {
+					Node::ParameterBlock { body } => body.as_slice(),
+					_ => std::slice::from_ref(parameter),
+				};
+				let formatted = format_items(items, indent, previous_ln);
+				previous_ln = items
+					.iter()
+					.rev()
+					.find(|n| !matches!(n.node, Node::EmptyLine))
+					.map(|n| n.ln)
+					.or(previous_ln);
+				formatted
+			})
+			.collect::<Vec<(Vec<String>, Vec<Line>)>>()
+	};
+
+	let values = format(indent);
+	if values
+		.iter()
+		.all(|(trailing, lines)| trailing.is_empty() && lines.is_empty())
+	{
+		return String::new();
+	}
+
+	let single_line = values.iter().all(|(trailing, lines)| {
+		trailing.iter().all(|comment| comment.starts_with("/*"))
+			&& lines.len() <= 1
+			&& lines.iter().all(|line| !line.closed)
+	});
+	if single_line {
+		let inline = format!(
+			"({})",
+			values
+				.iter()
+				.filter(|(_, lines)| !lines.is_empty())
+				.map(|(trailing, lines)| {
+					trailing
+						.iter()
+						.map(String::as_str)
+						.chain(lines.iter().map(|line| line.text.as_str()))
+						.collect::<Vec<&str>>()
+						.join(" ")
+				})
+				.collect::<Vec<String>>()
+				.join(", ")
+		);
+		let first_line = inline.lines().next().unwrap_or_default();
+		if values.len() == 1
+			|| inline.contains('\n')
+			|| indent * TAB_WIDTH + first_line.len() <= MAX_WIDTH
+		{
+			return inline;
+		}
+	}
+
+	let mut out: Vec<String> = Vec::new();
+	let mut closed = true;
+	for (trailing, lines) in format(indent + 1) {
+		for comment in trailing {
+			match out.last_mut() {
+				Some(line) if !closed => {
+					line.push(' ');
+					line.push_str(&comment);
+				}
+				_ => out.push(indent_str(indent + 1) + &comment),
+			}
+			closed = comment.starts_with("//");
+		}
+
+		let last_code = lines.iter().rposition(|line| line.code_end > 0);
+		for (i, line) in lines.iter().enumerate() {
+			let text = if Some(i) == last_code {
+				line.with_comma()
+			} else {
+				line.text.clone()
+			};
+			out.push(indent_str(indent + 1) + &text);
+			closed = line.closed;
+		}
+	}
+	format!("(\n{}\n{})", out.join("\n"), indent_str(indent))
+}
+
+/// A parenthesized group of nodes inside a parameter, as in `(1 /* c */)`.
+fn format_group(body: &[Box<PosNode>], indent: usize) -> String {
+	let (_, lines) = format_items(body, indent, None);
+	match lines.as_slice() {
+		[] => String::from("()"),
+		[line] if !line.closed => format!("({})", line.text),
+		_ => {
+			let (_, lines) = format_items(body, indent + 1, None);
+			let lines: Vec<String> = lines
+				.iter()
+				.map(|line| indent_str(indent + 1) + &line.text)
+				.collect();
+			format!("(\n{}\n{})", lines.join("\n"), indent_str(indent))
+		}
+	}
+}
+
+fn format_node(pos_node: &PosNode, indent: usize) -> String {
+	match &pos_node.node {
+		Node::FnCall {
+			name,
+			parameters,
+			body_fn,
+			doc,
+		} => {
+			let mut string = String::new();
+			for line in doc.iter().flat_map(|doc| doc.split('\n')) {
+				string += &(String::from("//:") + line.trim_end() + "\n" + &indent_str(indent));
+			}
+			string += &(name.clone() + &format_parameters(parameters, indent));
+			if let Some(body) = body_fn {
+				string += ": ";
+				string += &format_node(body, indent);
+			}
+			string
+		}
+		Node::Scope { body } => {
+			if body.iter().all(|n| matches!(n.node, Node::EmptyLine)) {
+				String::from("{}")
+			} else {
+				format!(
+					"{{\n{}\n{}}}",
+					format_body(body, indent + 1),
+					indent_str(indent)
+				)
+			}
+		}
+		Node::ParameterBlock { body } => format_group(body, indent),
+		Node::Program { body } => format_body(body, indent),
+		Node::FnAccess { target, call } => {
+			format_node(target, indent) + "." + &format_node(call, indent)
+		}
+		Node::Boolean(v) => v.to_string(),
+		Node::Number(v) => v.to_string(),
+		Node::String(v) => format!("\"{}\"", v),
+		Node::Name(v) => format!("<{}>", v),
+		Node::None => String::from("none"),
+		Node::Comment(text) => text.trim_end().to_string(),
+		Node::EmptyLine => String::new(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fmt(code: &str) -> String {
+		format(String::from(code)).unwrap()
+	}
+
+	#[test]
+	fn keeps_comments_in_parameters() {
+		for code in [
+			"print((1 /* inner */), 2)\n",
+			"print(1 /* c */, 2)\n",
+			"print(1, /* c */ 2)\n",
+			"print(map(\n\t// leading\n\t1, // one\n\t/* two */ 2, // after two\n\t3, // three\n))\n",
+			"print((\n\t1 // inner\n), 2)\n",
+		] {
+			assert_eq!(fmt(code), code);
+		}
+		assert_eq!(
+			fmt("print(map(\n1, // one\n2\n))"),
+			"print(map(\n\t1, // one\n\t2,\n))\n"
+		);
+		assert_eq!(fmt("f(a, b, // end\n)"), "f(\n\ta,\n\tb, // end\n)\n");
+	}
+
+	#[test]
+	fn keeps_comments_before_bodies() {
+		for code in [
+			"let(<x>): /* c */ 5\n",
+			"let(<x>): /* a */ /* b */ 5\n",
+			"let(<x>): // c\n\t5\n",
+			"if(true): /* c */ {\n\tprint(1)\n} else: {\n\tprint(2)\n}\n",
+		] {
+			assert_eq!(fmt(code), code);
+		}
+	}
+
+	#[test]
+	fn keeps_statement_comments() {
+		let code = "// top\nlet(<x>): 1 // trailing\n\n/* block */\nprint(x)\n";
+		assert_eq!(fmt(code), code);
+	}
+
+	#[test]
+	fn is_idempotent() {
+		let mut paths: Vec<_> = ["test", "test/my_module"]
+			.into_iter()
+			.flat_map(|dir| std::fs::read_dir(dir).unwrap())
+			.map(|entry| entry.unwrap().path())
+			.filter(|path| path.extension().is_some_and(|ext| ext == "bean"))
+			.collect();
+		paths.sort();
+		assert!(!paths.is_empty());
+
+		for path in paths {
+			let once = fmt(&std::fs::read_to_string(&path).unwrap());
+			assert_eq!(fmt(&once), once, "{}", path.display());
+		}
+	}
+}
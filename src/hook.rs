@@ -0,0 +1,136 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+	data::Data,
+	error::Error,
+	modules::{registry::ModuleRegistry, CustomModule},
+	parser::PosNode,
+	scope::{function::Function, ScopeRef},
+	util::{MaybeSync, MutRc, Shared},
+};
+
+/// Observes evaluation. Register hooks on a
+/// [`ModuleRegistry`](crate::modules::registry::ModuleRegistry) through its
+/// `hooks` field; they run for every module evaluated with that registry.
+/// Every callback does nothing by default.
+///
+/// `file` is the path of the module the node was written in. For the main
+/// program, that is the directory the program was run from.
+pub trait ExecutionHook: MaybeSync {
+	/// Called before a node is evaluated. `statement` is true for nodes
+	/// directly inside a program or block, as opposed to arguments and
+	/// values.
+	fn node_enter(&mut self, _file: &Path, _node: &PosNode, _scope: &ScopeRef, _statement: bool) {}
+
+	/// Called after a node is evaluated, whether or not it succeeded.
+	fn node_exit(&mut self, _file: &Path, _node: &PosNode, _result: &Result<Data, Error>) {}
+
+	/// Called before a function is called by name, after its arguments are
+	/// evaluated.
+	fn call(&mut self, _name: &str, _function: &Function, _args: &[Data], _scope: &ScopeRef) {}
+
+	/// Called after a function called by name returns.
+	fn call_return(&mut self, _name: &str, _function: &Function, _result: &Result<Data, Error>) {}
+
+	/// Called once for the innermost node an error was raised in, before it
+	/// propagates.
+	fn error(&mut self, _file: &Path, _node: &PosNode, _error: &Error) {}
+
+	/// Called when a module file is loaded, before it is evaluated.
+	fn module_load(&mut self, _path: &Path, _tree: &PosNode) {}
+}
+
+/// Runs `callback` on the module file `scope` belongs to.
+fn with_module<T>(scope: &ScopeRef, callback: impl FnOnce(&CustomModule) -> Option<T>) -> Option<T> {
+	// Scopes may already be borrowed while they call into functions, so the
+	// parent chain is walked without panicking on busy scopes.
+	let mut current = Shared::clone(scope);
+	loop {
+		let parent = {
+			let scope = current.try_borrow().ok()?;
+			if let Some(module) = scope.as_any().downcast_ref::<CustomModule>() {
+				return callback(module);
+			}
+			scope.parent()?
+		};
+		current = parent;
+	}
+}
+
+/// Path of the module file `scope` belongs to, as passed to hooks.
+pub fn module_path(scope: &ScopeRef) -> Option<PathBuf> {
+	with_module(scope, |module| Some(module.file_path.clone()))
+}
+
+/// The hooks of the registry a scope belongs to.
+pub(crate) struct Attached {
+	hooks: Vec<MutRc<dyn ExecutionHook>>,
+	registry: MutRc<ModuleRegistry>,
+	file: PathBuf,
+}
+
+impl Attached {
+	/// Finds the hooks for `scope`, if its registry has any.
+	pub(crate) fn find(scope: &ScopeRef) -> Option<Self> {
+		with_module(scope, |module| {
+			let registry = module.registry.try_borrow().ok()?;
+			if registry.hooks.is_empty() {
+				return None;
+			}
+			Some(Self {
+				hooks: registry.hooks.clone(),
+				registry: Shared::clone(&module.registry),
+				file: module.file_path.clone(),
+			})
+		})
+	}
+
+	fn each(&self, mut callback: impl FnMut(&mut dyn ExecutionHook)) {
+		for hook in &self.hooks {
+			// A hook that is busy is evaluating code itself, and does not
+			// observe its own evaluation.
+			if let Ok(mut hook) = hook.try_borrow_mut() {
+				callback(&mut *hook);
+			}
+		}
+	}
+
+	fn set_unwinding(&self, unwinding: bool) -> bool {
+		match self.registry.try_borrow_mut() {
+			Ok(mut registry) => std::mem::replace(&mut registry.unwinding, unwinding),
+			Err(_) => unwinding,
+		}
+	}
+
+	pub(crate) fn node_enter(&self, node: &PosNode, scope: &ScopeRef, statement: bool) {
+		self.set_unwinding(false);
+		self.each(|hook| hook.node_enter(&self.file, node, scope, statement));
+	}
+
+	pub(crate) fn node_exit(&self, node: &PosNode, result: &Result<Data, Error>) {
+		if let Err(error) = result {
+			if !self.set_unwinding(true) {
+				self.each(|hook| hook.error(&self.file, node, error));
+			}
+		}
+		self.each(|hook| hook.node_exit(&self.file, node, result));
+	}
+
+	pub(crate) fn call(&self, name: &str, function: &Function, args: &[Data], scope: &ScopeRef) {
+		self.each(|hook| hook.call(name, function, args, scope));
+	}
+
+	pub(crate) fn call_return(&self, name: &str, function: &Function, result: &Result<Data, Error>) {
+		self.each(|hook| hook.call_return(name, function, result));
+	}
+}
+
+/// Tells the hooks of `registry` that a module file was loaded.
+pub(crate) fn module_load(registry: &MutRc<ModuleRegistry>, path: &Path, tree: &PosNode) {
+	let hooks = registry.borrow().hooks.clone();
+	for hook in hooks {
+		if let Ok(mut hook) = hook.try_borrow_mut() {
+			hook.module_load(path, tree);
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use std::{rc::Rc, sync::Mutex};
+use std::sync::Mutex;
 
 use bean_script::{
 	data::Data,
@@ -6,7 +6,7 @@ use bean_script::{
 	modules::registry::{ModuleRegistry, RegistryFeatures},
 	parser::{self, Node, PosNode},
 	scope::{block_scope::BlockScope, ScopeRef},
-	util::make_ref,
+	util::{make_ref, Shared},
 };
 use rustyline::{error::ReadlineError, DefaultEditor};
 
@@ -32,7 +32,7 @@ pub fn open() -> rustyline::Result<()> {
 
 				let _ = rl.add_history_entry(line.as_str());
 
-				let scope_ref = Rc::clone(&mutex.lock().unwrap());
+				let scope_ref = Shared::clone(&mutex.lock().unwrap());
 
 				let tree = parser::parse(lexer::tokenize(line));
 				if let Err(error) = tree {
@@ -52,6 +52,7 @@ pub fn open() -> rustyline::Result<()> {
 						tree = PosNode {
 							node: Node::Program { body },
 							ln: 0,
+							start_ln: 0,
 						};
 					}
 				}
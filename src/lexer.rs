@@ -12,14 +12,18 @@ enum Context {
     BlockComment,
 }
 
-fn chunk(code: String) -> Vec<String> {
+/// Splits code into the chunks tokens are made from. Spaces and tabs outside
+/// strings and comments separate chunks and are dropped; everything else is
+/// kept, including comments when `keep_trivia` is set.
+pub(crate) fn chunk(code: String, keep_trivia: bool) -> Vec<String> {
     if code.contains('\r') {
-        println!("\x1b[33;1mwarn\x1b[0m: file contains CRLF line endings, which are not supported.")
+        eprintln!("\x1b[33;1mwarn\x1b[0m: file contains CRLF line endings, which are not supported.")
     }
 
     let mut chunks: Vec<String> = Vec::new();
     let current_chunk = RefCell::from(String::new());
     let mut context = Context::Program;
+    let mut keep_comment = keep_trivia;
     let chars: Vec<char> = code.chars().collect();
 
     let mut split = || {
@@ -39,11 +43,18 @@ fn chunk(code: String) -> Vec<String> {
                     split();
                     append(&char);
                     split();
-                } else if char == '/' && chars[i + 1] == '/' {
+                } else if char == '/' && chars.get(i + 1) == Some(&'/') {
                     split();
+                    keep_comment = keep_trivia || chars.get(i + 2) == Some(&':');
+                    if keep_comment {
+                        append(&char);
+                    }
                     context = Context::LineComment;
-                } else if char == '/' && chars[i + 1] == '*' {
+                } else if char == '/' && chars.get(i + 1) == Some(&'*') {
                     split();
+                    if keep_trivia {
+                        append(&char);
+                    }
                     context = Context::BlockComment;
                 } else if char == '"' {
                     split();
@@ -87,10 +98,18 @@ fn chunk(code: String) -> Vec<String> {
                     append(&char);
                     split();
                     context = Context::Program;
+                } else if keep_comment {
+                    append(&char);
                 }
             }
             Context::BlockComment => {
-                if chars[i - 1] == '*' && char == '/' {
+                if keep_trivia {
+                    append(&char);
+                    if chars[i - 1] == '*' && char == '/' {
+                        split();
+                        context = Context::Program;
+                    }
+                } else if chars[i - 1] == '*' && char == '/' {
                     context = Context::Program;
                 } else if char == '\n' {
                     split();
@@ -105,7 +124,7 @@ fn chunk(code: String) -> Vec<String> {
     return chunks;
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     FnName(String),
     FnBody,
@@ -124,55 +143,128 @@ pub enum Token {
 
     LineBreak,
     EOF,
+
+    Comment(String),
+    BlankLine,
+    DocComment(String),
 }
 
+/// Functions that define names, which doc comments can be attached to.
+const DEFINITIONS: [&str; 3] = ["fn", "let", "const"];
+
 pub fn tokenize(code: String) -> Vec<Token> {
-    let chunks = chunk(code);
+    tokenize_verbose(code, false)
+}
+
+/// Tokenizes code while keeping comments and blank lines as trivia tokens,
+/// so that tools like the formatter can reproduce them.
+pub fn tokenize_with_trivia(code: String) -> Vec<Token> {
+    tokenize_verbose(code, true)
+}
+
+fn tokenize_verbose(code: String, keep_trivia: bool) -> Vec<Token> {
+    let chunks = chunk(code, keep_trivia);
     let mut tokens: Vec<Token> = Vec::new();
 
     for chunk in chunks {
+        if keep_trivia && chunk == "\n" && matches!(tokens.last(), Some(Token::LineBreak)) {
+            tokens.push(Token::BlankLine);
+        }
+
         tokens.push(if chunk == "\n" {
             Token::LineBreak
-        } else if let Ok(n) = chunk.parse::<f64>() {
-            if tokens
-                .last()
-                .is_some_and(|x| pat_check!(Token::Accessor = x))
-            {
-                Token::FnName(chunk)
-            } else {
-                Token::Number(n)
-            }
-        } else if chunk.starts_with('"') && chunk.ends_with('"') {
-            Token::String(String::from(chunk.trim_matches('"')))
-        } else if chunk == "true" || chunk == "false" {
-            Token::Boolean(chunk == "true")
-        } else if chunk == "none" {
-            Token::None
-        } else if chunk.starts_with('<') && chunk.ends_with('>') {
-            Token::Name(String::from(chunk.trim_matches(['<', '>'])))
-        } else if chunk == ":" {
-            Token::FnBody
-        } else if chunk == "," {
-            Token::ArgSeparator
-        } else if chunk == "(" {
-            Token::ArgOpen
-        } else if chunk == ")" {
-            Token::ArgClose
-        } else if chunk == "{" {
-            Token::ScopeOpen
-        } else if chunk == "}" {
-            Token::ScopeClose
-        } else if chunk == "." {
-            Token::Accessor
+        } else if let Some(doc) = chunk.strip_prefix("//:") {
+            Token::DocComment(String::from(doc))
+        } else if keep_trivia && (chunk.starts_with("//") || chunk.starts_with("/*")) {
+            Token::Comment(chunk)
         } else {
-            Token::FnName(chunk)
+            token_from_chunk(chunk, tokens.last())
         });
     }
 
-    while let Some(Token::LineBreak) = tokens.last() {
+    let mut tokens = attach_doc_comments(tokens, keep_trivia);
+    while let Some(Token::LineBreak | Token::BlankLine) = tokens.last() {
         tokens.pop();
     }
     tokens.push(Token::EOF);
 
     return tokens;
 }
+
+/// Joins consecutive `//:` lines into a single doc comment token. Doc comments
+/// are only kept when they sit directly above a `fn`, `let` or `const` call;
+/// other doc comments are treated like regular comments.
+pub(crate) fn attach_doc_comments(tokens: Vec<Token>, keep_trivia: bool) -> Vec<Token> {
+    let mut result: Vec<Token> = Vec::new();
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let Token::DocComment(first) = &tokens[i] else {
+            result.push(tokens[i].clone());
+            i += 1;
+            continue;
+        };
+
+        let mut lines = vec![first.clone()];
+        i += 1;
+        while let (Some(Token::LineBreak), Some(Token::DocComment(line))) =
+            (tokens.get(i), tokens.get(i + 1))
+        {
+            lines.push(line.clone());
+            i += 2;
+        }
+
+        let attached = matches!(tokens.get(i), Some(Token::LineBreak))
+            && matches!(tokens.get(i + 1), Some(Token::FnName(name)) if DEFINITIONS.contains(&name.as_str()));
+        if attached {
+            result.push(Token::DocComment(lines.join("\n")));
+        } else {
+            for (n, line) in lines.into_iter().enumerate() {
+                if n > 0 {
+                    result.push(Token::LineBreak);
+                }
+                if keep_trivia {
+                    result.push(Token::Comment(String::from("//:") + &line));
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Classifies a single chunk of source code. `previous` is the token before
+/// it, since numbers after a dot are list indices rather than values.
+pub(crate) fn token_from_chunk(chunk: String, previous: Option<&Token>) -> Token {
+    if let Ok(n) = chunk.parse::<f64>() {
+        if previous.is_some_and(|x| pat_check!(Token::Accessor = x)) {
+            Token::FnName(chunk)
+        } else {
+            Token::Number(n)
+        }
+    } else if chunk.starts_with('"') && chunk.ends_with('"') {
+        Token::String(String::from(chunk.trim_matches('"')))
+    } else if chunk == "true" || chunk == "false" {
+        Token::Boolean(chunk == "true")
+    } else if chunk == "none" {
+        Token::None
+    } else if chunk.starts_with('<') && chunk.ends_with('>') {
+        Token::Name(String::from(chunk.trim_matches(['<', '>'])))
+    } else if chunk == ":" {
+        Token::FnBody
+    } else if chunk == "," {
+        Token::ArgSeparator
+    } else if chunk == "(" {
+        Token::ArgOpen
+    } else if chunk == ")" {
+        Token::ArgClose
+    } else if chunk == "{" {
+        Token::ScopeOpen
+    } else if chunk == "}" {
+        Token::ScopeClose
+    } else if chunk == "." {
+        Token::Accessor
+    } else {
+        Token::FnName(chunk)
+    }
+}
@@ -2,11 +2,29 @@ use error::Error;
 use modules::CustomModule;
 use util::MutRc;
 
+#[cfg(feature = "derive")]
+pub use bean_script_derive::BeanScope;
+
+pub mod beanc;
+pub mod checker;
+pub mod compiled;
+pub mod convert;
+pub mod coverage;
+pub mod cst;
 pub mod data;
+pub mod engine;
+pub mod debugger;
+pub mod doc;
 pub mod error;
+pub mod formatter;
+pub mod hook;
 pub mod logger;
 pub mod modules;
+pub mod profiler;
 pub mod scope;
+#[cfg(feature = "serde")]
+pub mod serde_data;
+pub mod user_data;
 pub mod util;
 
 pub mod evaluator;
@@ -0,0 +1,657 @@
+use std::{
+	cell::RefCell,
+	collections::HashMap,
+	fs,
+	io,
+	path::{Path, PathBuf},
+};
+
+use bean_script::{
+	checker::{self, Severity},
+	cst::{self, SyntaxKind, SyntaxToken},
+	doc, lexer,
+	modules::{
+		bean_std,
+		registry::{ModuleRegistry, RegistryFeatures},
+	},
+	parser::{self, PosNode},
+	scope::Scope,
+	util::Lock,
+};
+use serde_json::{json, Value};
+
+use crate::rpc::{read_message, send};
+
+const COMPLETION_FUNCTION: u8 = 3;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_MODULE: u8 = 9;
+
+/// A name defined by `fn`, `let` or `const`, or a parameter of `fn` or
+/// `lambda`.
+struct Definition {
+	name: String,
+	kind: String,
+	token: SyntaxToken,
+	/// Range of token indices where the name is in scope: the enclosing
+	/// block, or the body of the function for a parameter. The cursor is
+	/// in scope when the number of tokens before it is within the range.
+	scope: (usize, usize),
+}
+
+/// A name bound by `use`.
+struct Import {
+	name: String,
+	file: PathBuf,
+	member: Option<String>,
+	token: SyntaxToken,
+}
+
+/// Everything the server knows about one file.
+struct Analysis {
+	tokens: Vec<SyntaxToken>,
+	tree: Option<PosNode>,
+	definitions: Vec<Definition>,
+	imports: Vec<Import>,
+	exports: HashMap<String, String>,
+}
+
+impl Analysis {
+	fn new(text: &str, path: &Path) -> Self {
+		let tokens: Vec<SyntaxToken> = cst::lex(text)
+			.into_iter()
+			.filter(|t| !t.kind.is_trivia())
+			.collect();
+		let tree = parser::parse(lexer::tokenize(String::from(text))).ok();
+		let mut analysis = Analysis {
+			tokens,
+			tree,
+			definitions: Vec::new(),
+			imports: Vec::new(),
+			exports: HashMap::new(),
+		};
+		analysis.collect(path.parent().unwrap_or(Path::new(".")));
+		analysis
+	}
+
+	fn read(path: &Path) -> Option<Self> {
+		let text = fs::read_to_string(path).ok()?.replace('\r', "");
+		Some(Self::new(&text, path))
+	}
+
+	fn kind(&self, i: usize) -> Option<SyntaxKind> {
+		self.tokens.get(i).map(|t| t.kind)
+	}
+
+	fn text(&self, i: usize) -> Option<&str> {
+		self.tokens.get(i).map(|t| t.text.as_str())
+	}
+
+	/// Index of the token that closes the bracket opened at `open`.
+	fn closing(&self, open: usize) -> usize {
+		let mut depth = 0;
+		for (i, token) in self.tokens.iter().enumerate().skip(open) {
+			match token.kind {
+				SyntaxKind::ArgOpen | SyntaxKind::ScopeOpen => depth += 1,
+				SyntaxKind::ArgClose | SyntaxKind::ScopeClose => {
+					depth -= 1;
+					if depth == 0 {
+						return i;
+					}
+				}
+				_ => (),
+			}
+		}
+		self.tokens.len()
+	}
+
+	/// The innermost `{ }` block around token `i`, or the whole file.
+	fn enclosing_block(&self, i: usize) -> (usize, usize) {
+		let mut depth = 0;
+		for open in (0..i).rev() {
+			match self.tokens[open].kind {
+				SyntaxKind::ScopeClose => depth += 1,
+				SyntaxKind::ScopeOpen if depth == 0 => return (open + 1, self.closing(open)),
+				SyntaxKind::ScopeOpen => depth -= 1,
+				_ => (),
+			}
+		}
+		(0, self.tokens.len())
+	}
+
+	/// The body of the call whose arguments close at `close`: a block, or
+	/// the expression after the colon.
+	fn body(&self, close: usize) -> (usize, usize) {
+		if self.kind(close + 1) != Some(SyntaxKind::FnBody) {
+			return (close + 1, close);
+		}
+		match self.kind(close + 2) {
+			Some(SyntaxKind::ScopeOpen) => (close + 3, self.closing(close + 2)),
+			Some(_) => (close + 2, self.expression_end(close + 2) + 1),
+			None => (close + 1, close),
+		}
+	}
+
+	/// Index of the last token of the expression starting at `start`,
+	/// including bodies and accessed members.
+	fn expression_end(&self, start: usize) -> usize {
+		let mut j = start;
+		loop {
+			if self.kind(j) == Some(SyntaxKind::ScopeOpen) {
+				j = self.closing(j);
+			} else if self.kind(j + 1) == Some(SyntaxKind::ArgOpen) {
+				j = self.closing(j + 1);
+				if self.kind(j + 1) == Some(SyntaxKind::FnBody) {
+					j = self.expression_end(j + 2);
+				}
+			}
+			if self.kind(j + 1) == Some(SyntaxKind::Accessor) {
+				j += 2;
+			} else {
+				return j.min(self.tokens.len());
+			}
+		}
+	}
+
+	fn collect(&mut self, dir: &Path) {
+		for i in 0..self.tokens.len() {
+			if self.kind(i) != Some(SyntaxKind::FnName)
+				|| self.kind(i + 1) != Some(SyntaxKind::ArgOpen)
+			{
+				continue;
+			}
+			let close = self.closing(i + 1);
+
+			match self.text(i) {
+				Some(kind @ ("fn" | "let" | "const" | "lambda")) => {
+					let kind = String::from(kind);
+					let mut first = kind != "lambda";
+					let block = self.enclosing_block(i);
+					let body = self.body(close);
+					for j in i + 2..close {
+						let token = &self.tokens[j];
+						if token.kind != SyntaxKind::Name {
+							continue;
+						}
+						self.definitions.push(Definition {
+							name: name_of(&token.text),
+							kind: if first {
+								kind.clone()
+							} else {
+								String::from("parameter")
+							},
+							token: token.clone(),
+							scope: if first { block } else { body },
+						});
+						first = false;
+					}
+				}
+				Some("use") if self.kind(i + 2) == Some(SyntaxKind::String) => {
+					let token = self.tokens[i + 2].clone();
+					let id = token.text.trim_matches('"');
+					let (path, member) = match id.split_once(':') {
+						Some((path, member)) => (path, Some(member)),
+						None => (id, None),
+					};
+					let Some(relative) = path.strip_prefix("./") else {
+						continue;
+					};
+					let mut file = dir.join(relative);
+					file.set_extension("bean");
+
+					let alias = (self.kind(i + 3) == Some(SyntaxKind::ArgSeparator)
+						&& self.kind(i + 4) == Some(SyntaxKind::Name))
+					.then(|| name_of(&self.tokens[i + 4].text));
+					let name = match (alias, member) {
+						(Some(alias), _) => alias,
+						(None, Some(member)) => String::from(member),
+						(None, None) => String::from(path.rsplit('/').next().unwrap_or(path)),
+					};
+					if matches!(member, Some("*" | "")) {
+						continue;
+					}
+
+					self.imports.push(Import {
+						name,
+						file,
+						member: member.map(String::from),
+						token,
+					});
+				}
+				Some("export") if self.kind(i + 2) == Some(SyntaxKind::Name) => {
+					let name = name_of(&self.tokens[i + 2].text);
+					let alias = if self.kind(i + 3) == Some(SyntaxKind::ArgSeparator)
+						&& self.kind(i + 4) == Some(SyntaxKind::String)
+					{
+						String::from(self.tokens[i + 4].text.trim_matches('"'))
+					} else {
+						name.clone()
+					};
+					self.exports.insert(alias, name);
+				}
+				_ => (),
+			}
+		}
+	}
+
+	/// Index of the token under a zero-based line and column.
+	fn token_at(&self, line: usize, character: usize) -> Option<usize> {
+		let find = |character: usize| {
+			self.tokens.iter().position(|t| {
+				t.span.ln == line + 1
+					&& t.span.col <= character + 1
+					&& character + 1 < t.span.col + t.text.chars().count()
+			})
+		};
+		find(character).or_else(|| character.checked_sub(1).and_then(find))
+	}
+
+	/// The definition `name` refers to at token `i`: the closest definition
+	/// before it, or the first one after it since definitions are hoisted.
+	fn definition(&self, name: &str, i: usize) -> Option<&Definition> {
+		let offset = self.tokens[i].span.start;
+		let candidates = || self.definitions.iter().filter(|d| d.name == name);
+		candidates()
+			.rev()
+			.find(|d| d.token.span.start <= offset)
+			.or_else(|| candidates().next())
+	}
+
+	/// The definition of an exported name, following export aliases.
+	fn exported(&self, name: &str) -> Option<&Definition> {
+		let name = self.exports.get(name).map_or(name, |n| n.as_str());
+		self.definitions
+			.iter()
+			.find(|d| d.name == name && d.kind != "parameter")
+	}
+
+	fn import(&self, name: &str) -> Option<&Import> {
+		self.imports.iter().rev().find(|i| i.name == name)
+	}
+
+	/// The module whose member is accessed at token `i`, as in `module.member`.
+	fn accessed_module(&self, i: usize) -> Option<&Import> {
+		if i < 2 || self.kind(i - 1) != Some(SyntaxKind::Accessor) {
+			return None;
+		}
+		self.import(&self.tokens[i - 2].text)
+			.filter(|import| import.member.is_none())
+	}
+}
+
+fn name_of(text: &str) -> String {
+	let text = text.trim_start_matches('<').trim_end_matches('>');
+	String::from(text.split(':').next().unwrap_or(text).trim())
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+	let path = uri.strip_prefix("file://").unwrap_or(uri);
+	let bytes = path.as_bytes();
+	let mut decoded = Vec::new();
+	let mut i = 0;
+	while i < bytes.len() {
+		let hex = path
+			.get(i + 1..i + 3)
+			.and_then(|h| u8::from_str_radix(h, 16).ok());
+		match (bytes[i], hex) {
+			(b'%', Some(byte)) => {
+				decoded.push(byte);
+				i += 3;
+			}
+			(byte, _) => {
+				decoded.push(byte);
+				i += 1;
+			}
+		}
+	}
+	PathBuf::from(String::from_utf8_lossy(&decoded).to_string())
+}
+
+fn path_to_uri(path: &Path) -> String {
+	let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+	let mut uri = String::from("file://");
+	for byte in path.to_string_lossy().bytes() {
+		if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+			uri.push(byte as char);
+		} else {
+			uri += &format!("%{:02X}", byte);
+		}
+	}
+	uri
+}
+
+fn range(token: &SyntaxToken) -> Value {
+	json!({
+		"start": { "line": token.span.ln - 1, "character": token.span.col - 1 },
+		"end": {
+			"line": token.span.ln - 1,
+			"character": token.span.col - 1 + token.text.chars().count(),
+		},
+	})
+}
+
+fn location(file: &Path, token: Option<&SyntaxToken>) -> Value {
+	json!({
+		"uri": path_to_uri(file),
+		"range": token.map_or_else(
+			|| json!({
+				"start": { "line": 0, "character": 0 },
+				"end": { "line": 0, "character": 0 },
+			}),
+			range,
+		),
+	})
+}
+
+fn hover_text(tree: Option<&PosNode>, name: &str) -> Option<String> {
+	let item = doc::find(tree?, name)?;
+	let mut text = format!("```bean\n{}\n```", item.usage);
+	if !item.description.is_empty() {
+		text += "\n\n";
+		text += &item.description.join("\n");
+	}
+	Some(text)
+}
+
+struct Server {
+	registry: ModuleRegistry,
+	documents: HashMap<String, String>,
+	analyses: RefCell<HashMap<String, Analysis>>,
+}
+
+impl Server {
+	fn analyze(&self, uri: &str) -> Option<()> {
+		if !self.analyses.borrow().contains_key(uri) {
+			let text = self.documents.get(uri)?;
+			let analysis = Analysis::new(text, &uri_to_path(uri));
+			self.analyses
+				.borrow_mut()
+				.insert(String::from(uri), analysis);
+		}
+		Some(())
+	}
+
+	fn publish_diagnostics(&self, uri: &str) -> io::Result<()> {
+		let text = self.documents.get(uri).cloned().unwrap_or_default();
+		let lines: Vec<&str> = text.lines().collect();
+		let diagnostic = |ln: usize, severity: u8, message: &str| {
+			let line = ln.max(1) - 1;
+			json!({
+				"range": {
+					"start": { "line": line, "character": 0 },
+					"end": {
+						"line": line,
+						"character": lines.get(line).map_or(0, |l| l.chars().count()),
+					},
+				},
+				"severity": severity,
+				"source": "beans",
+				"message": message,
+			})
+		};
+
+		let diagnostics: Vec<Value> = match parser::parse(lexer::tokenize(text.clone())) {
+			Ok(tree) => checker::check(&tree, &self.registry)
+				.iter()
+				.map(|d| {
+					let severity = match d.severity {
+						Severity::Error => 1,
+						Severity::Warning => 2,
+					};
+					diagnostic(d.ln, severity, &d.msg)
+				})
+				.collect(),
+			Err(error) => vec![diagnostic(error.line().unwrap_or(1), 1, error.message())],
+		};
+
+		send(json!({
+			"jsonrpc": "2.0",
+			"method": "textDocument/publishDiagnostics",
+			"params": { "uri": uri, "diagnostics": diagnostics },
+		}))
+	}
+
+	fn definition(&self, uri: &str, line: usize, character: usize) -> Option<Value> {
+		self.analyze(uri)?;
+		let analyses = self.analyses.borrow();
+		let analysis = analyses.get(uri)?;
+		let path = uri_to_path(uri);
+		let i = analysis.token_at(line, character)?;
+		let token = &analysis.tokens[i];
+
+		if token.kind == SyntaxKind::String {
+			let import = analysis.imports.iter().find(|i| i.token == *token)?;
+			let member = import
+				.member
+				.as_ref()
+				.and_then(|m| Some(Analysis::read(&import.file)?.exported(m)?.token.clone()));
+			return Some(location(&import.file, member.as_ref()));
+		}
+
+		let name = name_of(&token.text);
+		if let Some(import) = analysis.accessed_module(i) {
+			let module = Analysis::read(&import.file)?;
+			return Some(location(
+				&import.file,
+				module.exported(&name).map(|d| &d.token),
+			));
+		}
+		if let Some(definition) = analysis.definition(&name, i) {
+			return Some(location(&path, Some(&definition.token)));
+		}
+
+		let import = analysis.import(&name)?;
+		let member = match &import.member {
+			Some(member) => Analysis::read(&import.file)?
+				.exported(member)
+				.map(|d| d.token.clone()),
+			None => None,
+		};
+		Some(location(&import.file, member.as_ref()))
+	}
+
+	fn hover(&self, uri: &str, line: usize, character: usize) -> Option<Value> {
+		self.analyze(uri)?;
+		let analyses = self.analyses.borrow();
+		let analysis = analyses.get(uri)?;
+		let i = analysis.token_at(line, character)?;
+		let token = &analysis.tokens[i];
+		if !matches!(token.kind, SyntaxKind::FnName | SyntaxKind::Name) {
+			return None;
+		}
+		let name = name_of(&token.text);
+
+		let text = if let Some(import) = analysis.accessed_module(i) {
+			let module = Analysis::read(&import.file)?;
+			let target = module.exports.get(&name).unwrap_or(&name);
+			hover_text(module.tree.as_ref(), target)?
+		} else if analysis.definition(&name, i).is_some() {
+			hover_text(analysis.tree.as_ref(), &name)?
+		} else if let Some(import) = analysis.import(&name) {
+			let module = Analysis::read(&import.file)?;
+			match &import.member {
+				Some(member) => {
+					let target = module.exports.get(member).unwrap_or(member);
+					hover_text(module.tree.as_ref(), target)?
+				}
+				None => format!("module `{}`", import.file.display()),
+			}
+		} else {
+			format!("```bean\n{}\n```", bean_std::builtin_signature(&name)?)
+		};
+
+		Some(json!({
+			"contents": { "kind": "markdown", "value": text },
+			"range": range(token),
+		}))
+	}
+
+	fn completion(&self, uri: &str, line: usize, character: usize) -> Option<Value> {
+		self.analyze(uri)?;
+		let analyses = self.analyses.borrow();
+		let analysis = analyses.get(uri)?;
+		let mut items = Vec::new();
+
+		// The completed word may be empty, right after a dot.
+		let dot = analysis
+			.tokens
+			.iter()
+			.rposition(|t| {
+				t.span.ln == line + 1 && t.span.col <= character && t.kind == SyntaxKind::Accessor
+			})
+			.filter(|&dot| {
+				let end = analysis.tokens[dot].span.col;
+				analysis.tokens[dot + 1..]
+					.iter()
+					.take_while(|t| t.span.ln == line + 1 && t.span.col <= character)
+					.all(|t| t.kind == SyntaxKind::FnName && t.span.col > end)
+			});
+		if let Some(import) = dot.and_then(|dot| analysis.accessed_module(dot + 1)) {
+			let module = Analysis::read(&import.file)?;
+			for item in module.tree.as_ref().map(doc::collect).unwrap_or_default() {
+				items.push(json!({
+					"label": item.name,
+					"kind": COMPLETION_FUNCTION,
+					"detail": item.usage,
+					"documentation": item.description.join("\n"),
+				}));
+			}
+			return Some(json!(items));
+		}
+
+		// Number of tokens before the cursor, to find the definitions in scope.
+		let cursor = analysis
+			.tokens
+			.iter()
+			.take_while(|t| {
+				t.span.ln < line + 1 || (t.span.ln == line + 1 && t.span.col <= character)
+			})
+			.count();
+		let mut seen = Vec::new();
+		for definition in analysis.definitions.iter().rev() {
+			let (start, end) = definition.scope;
+			if seen.contains(&definition.name) || cursor < start || cursor > end {
+				continue;
+			}
+			seen.push(definition.name.clone());
+			let kind = if definition.kind == "fn" {
+				COMPLETION_FUNCTION
+			} else {
+				COMPLETION_VARIABLE
+			};
+			items.push(json!({
+				"label": definition.name,
+				"kind": kind,
+				"detail": definition.kind,
+			}));
+		}
+		for import in &analysis.imports {
+			items.push(json!({
+				"label": import.name,
+				"kind": COMPLETION_MODULE,
+				"detail": import.file.display().to_string(),
+			}));
+		}
+
+		let mut builtins: Vec<String> = Lock::borrow(&self.registry.runtime())
+			.get_function_list()
+			.into_keys()
+			.collect();
+		builtins.sort();
+		for name in builtins {
+			items.push(json!({
+				"label": name,
+				"kind": COMPLETION_FUNCTION,
+				"detail": bean_std::builtin_signature(&name),
+			}));
+		}
+
+		Some(json!(items))
+	}
+}
+
+/// Runs a language server over stdin and stdout until the client exits.
+pub fn run() -> io::Result<()> {
+	let mut server = Server {
+		registry: ModuleRegistry::new(RegistryFeatures::default()),
+		documents: HashMap::new(),
+		analyses: RefCell::new(HashMap::new()),
+	};
+	let mut shutdown = false;
+	let mut stdin = io::stdin().lock();
+
+	while let Some(message) = read_message(&mut stdin)? {
+		let method = message["method"].as_str().unwrap_or_default();
+		let params = &message["params"];
+		let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+		let line = params["position"]["line"].as_u64().unwrap_or(0) as usize;
+		let character = params["position"]["character"].as_u64().unwrap_or(0) as usize;
+
+		let result = match method {
+			"initialize" => json!({
+				"capabilities": {
+					"textDocumentSync": 1,
+					"hoverProvider": true,
+					"definitionProvider": true,
+					"completionProvider": { "triggerCharacters": ["."] },
+				},
+				"serverInfo": { "name": "beans", "version": env!("CARGO_PKG_VERSION") },
+			}),
+			"shutdown" => {
+				shutdown = true;
+				Value::Null
+			}
+			"exit" => break,
+			"textDocument/didOpen" | "textDocument/didChange" => {
+				let text = if method == "textDocument/didOpen" {
+					params["textDocument"]["text"].as_str()
+				} else {
+					params["contentChanges"]
+						.as_array()
+						.and_then(|c| c.last())
+						.and_then(|c| c["text"].as_str())
+				};
+				server.documents.insert(
+					String::from(uri),
+					text.unwrap_or_default().replace('\r', ""),
+				);
+				server.analyses.get_mut().remove(uri);
+				server.publish_diagnostics(uri)?;
+				continue;
+			}
+			"textDocument/didClose" => {
+				server.documents.remove(uri);
+				server.analyses.get_mut().remove(uri);
+				continue;
+			}
+			"textDocument/definition" => {
+				server.definition(uri, line, character).unwrap_or_default()
+			}
+			"textDocument/hover" => server.hover(uri, line, character).unwrap_or_default(),
+			"textDocument/completion" => {
+				server.completion(uri, line, character).unwrap_or_default()
+			}
+			_ if message["id"].is_null() => continue,
+			_ => {
+				send(json!({
+					"jsonrpc": "2.0",
+					"id": message["id"],
+					"error": { "code": -32601, "message": format!("Unknown method {}.", method) },
+				}))?;
+				continue;
+			}
+		};
+
+		if !message["id"].is_null() {
+			send(json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }))?;
+		}
+	}
+
+	if shutdown {
+		Ok(())
+	} else {
+		Err(io::Error::new(
+			io::ErrorKind::UnexpectedEof,
+			"Client exited without shutting down.",
+		))
+	}
+}
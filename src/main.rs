@@ -1,31 +1,74 @@
 use std::{
 	env::{self, Args},
+	ffi::OsString,
 	fs,
 	path::PathBuf,
 	process::ExitCode,
 };
 
 use bean_script::{
+	beanc,
+	checker::{self, Severity},
+	compiled::CompiledScript,
+	coverage::Coverage,
+	doc::{self, DocFormat},
 	error::{BeanResult, ErrorSource},
-	evaluator, lexer,
+	evaluator, formatter, lexer,
 	modules::{
 		registry::{ModuleRegistry, RegistryFeatures},
 		CustomModule,
 	},
 	parser,
+	profiler::Profiler,
 	util::make_ref,
 };
 
+#[cfg(feature = "lsp")]
+mod dap;
 mod interactive_terminal;
+#[cfg(feature = "lsp")]
+mod lsp;
+#[cfg(feature = "lsp")]
+mod rpc;
+mod test_runner;
 
 const HELP_MSG: &str = "Function-based language interpreter.
 Usage: beans [OPTIONS] [PATH]
+       beans <COMMAND> [PATH]
+
+Commands:
+	check           Report mistakes in a file without running it.
+	compile         Write the parsed script to a .beanc file, which runs
+	                and loads as a module without being parsed again.
+	                Use -o to choose the output file.
+	fmt             Format files in place. With --check, only report
+	                files that are not formatted. Calls without
+	                arguments lose their parentheses: x() becomes x.
+	doc             Print documentation for the exported functions of
+	                modules as Markdown, or as HTML with --html.
+	lsp             Start a language server on stdin and stdout.
+	debug           Start a debug adapter on stdin and stdout.
+	test            Run the tests in *_test.bean files under the given
+	                directories, or the current directory. With
+	                --coverage, also report coverage. With --snapshot,
+	                compare the output of scripts with the .out files
+	                next to them instead, or update them with --bless.
 
 Options:
 	-p, --parse     Parse file without evaluating it.
 	-l, --tokenize  Tokenize file without parsing it.
 	-h, --help      Print this message and exit.
-	-i, --stdin     Interpret input from stdin.";
+	-i, --stdin     Interpret input from stdin.
+	-o, --output    File to write compile output to.
+	-I <DIR>        Look for modules in DIR, before the directories in
+	                BEAN_PATH. Can be given more than once.
+	--profile       Print time spent in each function and the most run
+	                lines, and write folded call stacks to
+	                <name>.folded for flamegraph tools.
+	--coverage      Print the fraction of lines run in each file, and
+	                write lcov coverage to lcov.info.";
+
+const COMMANDS: [&str; 7] = ["check", "compile", "fmt", "doc", "lsp", "debug", "test"];
 
 struct CliArgs {
 	no_args: bool,
@@ -33,7 +76,16 @@ struct CliArgs {
 	f_parse: bool,
 	f_tokenize: bool,
 	f_stdin: bool,
-	path: Option<String>,
+	f_check: bool,
+	f_html: bool,
+	f_profile: bool,
+	f_coverage: bool,
+	f_snapshot: bool,
+	f_bless: bool,
+	command: Option<String>,
+	paths: Vec<String>,
+	output: Option<String>,
+	includes: Vec<PathBuf>,
 }
 
 fn main() -> ExitCode {
@@ -53,65 +105,341 @@ fn main() -> ExitCode {
 			ExitCode::SUCCESS
 		}
 	} else {
-		let path_str = args.path.expect("Expected path to file.");
-		let file = fs::read_to_string(path_str.clone()).expect("Failed to open file");
+		match args.command.as_deref() {
+			Some("check") => check(args),
+			Some("compile") => compile(args),
+			Some("fmt") => fmt(args),
+			Some("doc") => doc(args),
+			Some("test") if args.f_snapshot || args.f_bless => {
+				test_runner::snapshot(&args.paths, &args.includes, args.f_bless)
+			}
+			Some("test") => test_runner::run(&args.paths, &args.includes, args.f_coverage),
+			#[cfg(feature = "lsp")]
+			Some("lsp") => {
+				if let Err(error) = lsp::run() {
+					eprintln!("\x1b[31;1merror\x1b[0m: Language server stopped.\n\t{}", error);
+					ExitCode::FAILURE
+				} else {
+					ExitCode::SUCCESS
+				}
+			}
+			#[cfg(feature = "lsp")]
+			Some("debug") => {
+				if let Err(error) = dap::run() {
+					eprintln!("\x1b[31;1merror\x1b[0m: Debug adapter stopped.\n\t{}", error);
+					ExitCode::FAILURE
+				} else {
+					ExitCode::SUCCESS
+				}
+			}
+			#[cfg(not(feature = "lsp"))]
+			Some(command @ ("lsp" | "debug")) => {
+				eprintln!(
+					"\x1b[31;1merror\x1b[0m: beans was built without the lsp feature, so {} is not available.",
+					command
+				);
+				ExitCode::FAILURE
+			}
+			_ => run(args),
+		}
+	}
+}
 
+fn run(args: CliArgs) -> ExitCode {
+	let path_str = args.paths.first().expect("Expected path to file.").clone();
+	let bytes = fs::read(path_str.clone()).expect("Failed to open file");
+
+	let tree = if beanc::is_compiled(&bytes) {
+		beanc::decode(&bytes)
+	} else {
+		let file = String::from_utf8(bytes).expect("Failed to open file");
 		let tokens = lexer::tokenize(file);
 		if args.f_tokenize {
 			dbg!(tokens);
 			return ExitCode::SUCCESS;
 		}
+		parser::parse(tokens)
+	};
+	if let Err(error) = tree {
+		println!(
+			"\x1b[31;1merror\x1b[0m: {}",
+			error.trace(ErrorSource::File(path_str.clone()))
+		);
+		return ExitCode::FAILURE;
+	}
+	let tree = tree.unwrap();
+
+	if args.f_parse {
+		dbg!(tree);
+		return ExitCode::SUCCESS;
+	}
+
+	let mut dir_path = PathBuf::from(path_str.clone());
+	dir_path.pop();
+
+	let registry = make_ref(ModuleRegistry::new(RegistryFeatures::default()));
+	add_search_paths(&mut registry.borrow_mut(), &args.includes);
+	let profiler = if args.f_profile {
+		let profiler = make_ref(Profiler::new(PathBuf::from(path_str.clone())));
+		registry.borrow_mut().hooks.push(profiler.clone());
+		Some(profiler)
+	} else {
+		None
+	};
+	let coverage = if args.f_coverage {
+		let mut coverage = Coverage::new();
+		coverage.add_main(dir_path.clone(), PathBuf::from(path_str.clone()), &tree);
+		let coverage = make_ref(coverage);
+		registry.borrow_mut().hooks.push(coverage.clone());
+		Some(coverage)
+	} else {
+		None
+	};
+
+	let program_scope = CustomModule::new(registry, dir_path);
+	let result = evaluator::evaluate(&tree, make_ref(program_scope));
+
+	if let Some(profiler) = profiler {
+		let mut profiler = profiler.borrow_mut();
+		profiler.finish();
+		println!("\n{}", profiler.summary(10));
+
+		let mut folded_path = PathBuf::from(&path_str);
+		folded_path.set_extension("folded");
+		let folded_path = PathBuf::from(folded_path.file_name().unwrap_or_default());
+		match fs::write(&folded_path, profiler.folded()) {
+			Ok(_) => println!("Wrote call stacks to {}.", folded_path.display()),
+			Err(error) => println!(
+				"\x1b[31;1merror\x1b[0m: Failed to write {}.\n\t{}",
+				folded_path.display(),
+				error
+			),
+		}
+	}
 
-		let tree = parser::parse(tokens);
-		if let Err(error) = tree {
+	if let Some(coverage) = coverage {
+		write_coverage(&coverage.borrow());
+	}
+
+	if let Err(error) = result {
+		println!(
+			"\x1b[31;1merror\x1b[0m: {}",
+			error.trace(ErrorSource::File(path_str.clone()))
+		);
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+/// Searches the directories given with `-I` for modules, then those in
+/// `BEAN_PATH`.
+pub(crate) fn add_search_paths(registry: &mut ModuleRegistry, includes: &[PathBuf]) {
+	registry.search_paths = search_paths(includes, env::var_os("BEAN_PATH"));
+}
+
+fn search_paths(includes: &[PathBuf], bean_path: Option<OsString>) -> Vec<PathBuf> {
+	let bean_path: Vec<PathBuf> = bean_path
+		.map(|paths| env::split_paths(&paths).collect())
+		.unwrap_or_default();
+	includes.iter().cloned().chain(bean_path).collect()
+}
+
+pub(crate) fn write_coverage(coverage: &Coverage) {
+	println!();
+	for (file, hit, found) in coverage.summary() {
+		let percent = if found == 0 {
+			100.0
+		} else {
+			hit as f64 / found as f64 * 100.0
+		};
+		println!("{:>6.1}%  {}/{}  {}", percent, hit, found, file.display());
+	}
+
+	match fs::write("lcov.info", coverage.lcov()) {
+		Ok(_) => println!("Wrote coverage to lcov.info."),
+		Err(error) => println!(
+			"\x1b[31;1merror\x1b[0m: Failed to write lcov.info.\n\t{}",
+			error
+		),
+	}
+}
+
+fn check(args: CliArgs) -> ExitCode {
+	let registry = ModuleRegistry::new(RegistryFeatures::default());
+	let mut failed = false;
+
+	for path_str in &args.paths {
+		let file = fs::read_to_string(path_str).expect("Failed to open file");
+		let tree = match parser::parse(lexer::tokenize(file)) {
+			Ok(tree) => tree,
+			Err(error) => {
+				println!(
+					"\x1b[31;1merror\x1b[0m: {}",
+					error.trace(ErrorSource::File(path_str.clone()))
+				);
+				failed = true;
+				continue;
+			}
+		};
+
+		for diagnostic in checker::check(&tree, &registry) {
 			println!(
-				"\x1b[31;1merror\x1b[0m: {}",
-				error.trace(ErrorSource::File(path_str.clone()))
+				"{}\n\x1b[36m->\x1b[0m {}:{}",
+				diagnostic, path_str, diagnostic.ln
 			);
-			return ExitCode::FAILURE;
+			failed |= diagnostic.severity == Severity::Error;
 		}
-		let tree = tree.unwrap();
+	}
 
-		if args.f_parse {
-			dbg!(tree);
-			return ExitCode::SUCCESS;
-		}
+	if failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+fn compile(args: CliArgs) -> ExitCode {
+	if args.output.is_some() && args.paths.len() > 1 {
+		println!("\x1b[31;1merror\x1b[0m: -o can only be used with a single file.");
+		return ExitCode::FAILURE;
+	}
+	let mut failed = false;
 
-		let mut dir_path = PathBuf::from(path_str.clone());
-		dir_path.pop();
+	for path_str in &args.paths {
+		let bytes = match CompiledScript::compile_file(path_str).and_then(|s| s.to_bytes()) {
+			Ok(bytes) => bytes,
+			Err(error) => {
+				println!(
+					"\x1b[31;1merror\x1b[0m: {}",
+					error.trace(ErrorSource::File(path_str.clone()))
+				);
+				failed = true;
+				continue;
+			}
+		};
 
-		let registry = make_ref(ModuleRegistry::new(RegistryFeatures::default()));
-		let program_scope = CustomModule::new(registry, dir_path);
-		let result = evaluator::evaluate(&tree, make_ref(program_scope));
-		if let Err(error) = result {
+		let output = args
+			.output
+			.clone()
+			.map_or_else(|| PathBuf::from(path_str).with_extension("beanc"), PathBuf::from);
+		if let Err(error) = fs::write(&output, bytes) {
 			println!(
-				"\x1b[31;1merror\x1b[0m: {}",
-				error.trace(ErrorSource::File(path_str.clone()))
+				"\x1b[31;1merror\x1b[0m: Failed to write {}.\n\t{}",
+				output.display(),
+				error
 			);
-			ExitCode::FAILURE
+			failed = true;
+		}
+	}
+
+	if failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+fn fmt(args: CliArgs) -> ExitCode {
+	let mut failed = false;
+
+	for path_str in &args.paths {
+		let file = fs::read_to_string(path_str).expect("Failed to open file");
+		let formatted = match formatter::format(file.clone()) {
+			Ok(formatted) => formatted,
+			Err(error) => {
+				println!(
+					"\x1b[31;1merror\x1b[0m: {}",
+					error.trace(ErrorSource::File(path_str.clone()))
+				);
+				failed = true;
+				continue;
+			}
+		};
+
+		if formatted == file {
+			continue;
+		} else if args.f_check {
+			println!("{} is not formatted.", path_str);
+			failed = true;
 		} else {
-			ExitCode::SUCCESS
+			fs::write(path_str, formatted).expect("Failed to write file");
 		}
 	}
+
+	if failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
+}
+
+fn doc(args: CliArgs) -> ExitCode {
+	let format = if args.f_html {
+		DocFormat::Html
+	} else {
+		DocFormat::Markdown
+	};
+	let mut failed = false;
+
+	for path_str in &args.paths {
+		let file = fs::read_to_string(path_str).expect("Failed to open file");
+		let tree = match parser::parse(lexer::tokenize(file)) {
+			Ok(tree) => tree,
+			Err(error) => {
+				println!(
+					"\x1b[31;1merror\x1b[0m: {}",
+					error.trace(ErrorSource::File(path_str.clone()))
+				);
+				failed = true;
+				continue;
+			}
+		};
+
+		let module_name = PathBuf::from(path_str)
+			.file_stem()
+			.map_or(path_str.clone(), |s| s.to_string_lossy().to_string());
+		print!("{}", doc::render(&module_name, &doc::collect(&tree), format));
+	}
+
+	if failed {
+		ExitCode::FAILURE
+	} else {
+		ExitCode::SUCCESS
+	}
 }
 
 fn parse_args(mut args: Args) -> CliArgs {
 	let no_args = args.len() == 1;
 	let mut flags: Vec<String> = Vec::new();
-	let mut path: Option<String> = None;
+	let mut command: Option<String> = None;
+	let mut paths: Vec<String> = Vec::new();
+	let mut output: Option<String> = None;
+	let mut includes: Vec<PathBuf> = Vec::new();
 
 	args.next();
-	for arg in args {
-		if arg.starts_with("--") || arg.starts_with("-") {
+	while let Some(arg) = args.next() {
+		if arg == "-o" || arg == "--output" {
+			output = args.next();
+		} else if arg == "-I" {
+			includes.extend(args.next().map(PathBuf::from));
+		} else if let Some(dir) = arg.strip_prefix("-I") {
+			includes.push(PathBuf::from(dir));
+		} else if arg.starts_with("--") || arg.starts_with("-") {
 			flags.push(arg);
-		} else if path.is_none() {
-			path = Some(arg);
+		} else if command.is_none() && paths.is_empty() && COMMANDS.contains(&arg.as_str()) {
+			command = Some(arg);
+		} else {
+			paths.push(arg);
 		}
 	}
 
 	CliArgs {
 		no_args,
-		path,
+		command,
+		paths,
+		output,
+		includes,
 		f_help: flags.contains(&String::from("--help"))
 			|| flags.contains(&String::from("-h")),
 		f_parse: flags.contains(&String::from("--parse"))
@@ -120,5 +448,26 @@ fn parse_args(mut args: Args) -> CliArgs {
 			|| flags.contains(&String::from("-l")),
 		f_stdin: flags.contains(&String::from("--stdin"))
 			|| flags.contains(&String::from("-i")),
+		f_check: flags.contains(&String::from("--check")),
+		f_html: flags.contains(&String::from("--html")),
+		f_profile: flags.contains(&String::from("--profile")),
+		f_coverage: flags.contains(&String::from("--coverage")),
+		f_snapshot: flags.contains(&String::from("--snapshot")),
+		f_bless: flags.contains(&String::from("--bless")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn includes_come_before_bean_path() {
+		let bean_path = env::join_paths(["/lib/a", "/lib/b"]).unwrap();
+		assert_eq!(
+			search_paths(&[PathBuf::from("inc")], Some(bean_path)),
+			[PathBuf::from("inc"), PathBuf::from("/lib/a"), PathBuf::from("/lib/b")]
+		);
+		assert_eq!(search_paths(&[], None), Vec::<PathBuf>::new());
 	}
 }
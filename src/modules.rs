@@ -1,12 +1,15 @@
-use std::{
-	any::Any, cell::RefCell, collections::HashMap, fmt::Debug, path::PathBuf, rc::Rc,
-};
+use std::{any::Any, collections::HashMap, fmt::Debug, path::PathBuf};
 
 use crate::{
+	convert::{FromData, IntoArgs, TypedFunction},
 	data::Data,
-	error::Error,
-	scope::{block_scope::IfState, function::Function, Scope, ScopeRef},
-	util::{make_ref, MutRc},
+	error::{Error, ErrorSource},
+	scope::{
+		block_scope::IfState,
+		function::{BuiltinFn, Function},
+		Scope, ScopeRef,
+	},
+	util::{make_ref, Lock, MaybeSync, MutRc, Shared},
 };
 
 use self::registry::{ModuleRegistry, RegistryFeatures};
@@ -22,38 +25,66 @@ pub trait Module: Scope {
 	fn get_pub_function(&self, name: &str) -> Option<Function>;
 }
 
+/// Minimum and maximum number of arguments a builtin accepts. `None` as the
+/// maximum means it accepts any number.
+pub type Arity = (usize, Option<usize>);
+
 pub struct ModuleBuilder {
-	functions: HashMap<
-		String,
-		Rc<dyn Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error>>,
-	>,
-	submodules: HashMap<String, Rc<RefCell<BuiltinModule>>>,
+	functions: HashMap<String, Shared<BuiltinFn>>,
+	arities: HashMap<String, Arity>,
+	submodules: HashMap<String, Shared<Lock<BuiltinModule>>>,
 	features: RegistryFeatures,
 }
 
 impl ModuleBuilder {
 	pub fn function<F>(&mut self, name: &str, function: F) -> &mut Self
 	where
-		F: Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error> + 'static,
+		F: Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error> + MaybeSync + 'static,
 	{
-		self.functions.insert(String::from(name), Rc::new(function));
+		self.functions.insert(String::from(name), Shared::new(function));
 		self
 	}
 
+	/// Adds a function along with the number of arguments it accepts, which
+	/// `beans check` reports calls for.
+	pub fn function_with_arity<F>(&mut self, name: &str, arity: Arity, function: F) -> &mut Self
+	where
+		F: Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error> + MaybeSync + 'static,
+	{
+		self.arities.insert(String::from(name), arity);
+		self.function(name, function)
+	}
+
+	/// Adds a function whose arguments and return value are converted
+	/// automatically, e.g. `|a: f64, b: f64| a + b`. Calls with too many
+	/// arguments, missing arguments or arguments of the wrong type fail with
+	/// an error naming the function.
+	pub fn typed_function<Args, F>(&mut self, name: &str, function: F) -> &mut Self
+	where
+		F: TypedFunction<Args> + MaybeSync + 'static,
+	{
+		let fn_name = String::from(name);
+		self.function(name, move |args, _body_fn, _scope| {
+			function.call_typed(&fn_name, args)
+		})
+	}
+
 	pub fn submodule<F>(&mut self, name: &str, constructor: F) -> &mut Self
 	where
 		F: FnOnce(&mut ModuleBuilder),
 	{
 		let mut module = ModuleBuilder {
 			functions: HashMap::new(),
+			arities: HashMap::new(),
 			submodules: HashMap::new(),
 			features: self.features,
 		};
 		constructor(&mut module);
 		self.submodules.insert(
 			String::from(name),
-			Rc::new(RefCell::new(BuiltinModule {
+			Shared::new(Lock::new(BuiltinModule {
 				functions: module.functions,
+				arities: module.arities,
 				submodules: module.submodules,
 			})),
 		);
@@ -63,11 +94,9 @@ impl ModuleBuilder {
 
 #[derive(Clone)]
 pub struct BuiltinModule {
-	functions: HashMap<
-		String,
-		Rc<dyn Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error>>,
-	>,
-	submodules: HashMap<String, Rc<RefCell<BuiltinModule>>>,
+	functions: HashMap<String, Shared<BuiltinFn>>,
+	arities: HashMap<String, Arity>,
+	submodules: HashMap<String, Shared<Lock<BuiltinModule>>>,
 }
 
 impl BuiltinModule {
@@ -77,15 +106,22 @@ impl BuiltinModule {
 	) -> Self {
 		let mut module = ModuleBuilder {
 			functions: HashMap::new(),
+			arities: HashMap::new(),
 			submodules: HashMap::new(),
 			features,
 		};
 		constructor(&mut module);
 		Self {
 			functions: module.functions,
+			arities: module.arities,
 			submodules: module.submodules,
 		}
 	}
+
+	/// The arity a function was registered with, if any.
+	pub fn arity(&self, name: &str) -> Option<Arity> {
+		self.arities.get(name).copied()
+	}
 }
 
 impl Scope for BuiltinModule {
@@ -95,7 +131,7 @@ impl Scope for BuiltinModule {
 
 	fn get_function(&self, name: &str) -> Option<Function> {
 		self.functions.get(name).map(|x| Function::BuiltIn {
-			callback: Rc::clone(x),
+			callback: Shared::clone(x),
 		})
 	}
 
@@ -111,7 +147,7 @@ impl Scope for BuiltinModule {
 			map.insert(
 				k.clone(),
 				Function::BuiltIn {
-					callback: Rc::clone(fun),
+					callback: Shared::clone(fun),
 				},
 			);
 		}
@@ -141,7 +177,7 @@ impl Module for BuiltinModule {
 	fn get_submodule(&self, name: &str) -> Option<MutRc<dyn Module>> {
 		self.submodules
 			.get(name)
-			.map(|rc| Rc::clone(rc) as MutRc<dyn Module>)
+			.map(|rc| Shared::clone(rc) as MutRc<dyn Module>)
 	}
 
 	fn has_pub_function(&self, name: &str) -> bool {
@@ -175,6 +211,40 @@ impl CustomModule {
 		}
 	}
 
+	pub fn local_functions(&self) -> HashMap<String, Function> {
+		self.local_functions.borrow().clone()
+	}
+
+	/// A function defined at the module's top level, without copying the
+	/// others.
+	pub fn local_function(&self, name: &str) -> Option<Function> {
+		self.local_functions.borrow().get(name).cloned()
+	}
+
+	/// Finds a function the module exports, or else one defined at its top
+	/// level.
+	pub fn find_function(&self, name: &str) -> Option<Function> {
+		let exported = self.exported_functions.borrow().get(name).cloned();
+		exported.or_else(|| self.local_functions.borrow().get(name).cloned())
+	}
+
+	/// Calls a function found with [`CustomModule::find_function`], converting
+	/// its arguments and result.
+	pub fn call_function<R: FromData>(
+		this: &MutRc<Self>,
+		name: &str,
+		args: impl IntoArgs,
+	) -> Result<R, Error> {
+		let function = this.borrow().find_function(name).ok_or_else(|| {
+			Error::new(
+				&format!("Module does not define function {}.", name),
+				ErrorSource::Internal,
+			)
+		})?;
+		let result = function.call(args.into_args(), None, Shared::clone(this) as ScopeRef)?;
+		R::from_data(result)
+	}
+
 	// courtesy of [stack overflow](https://stackoverflow.com/a/64400756)
 	fn to_scope(it: MutRc<Self>) -> ScopeRef {
 		it
@@ -187,19 +257,19 @@ impl Scope for CustomModule {
 	}
 
 	fn get_function(&self, name: &str) -> Option<Function> {
-		let binding = RefCell::borrow(&self.local_functions);
+		let binding = Lock::borrow(&self.local_functions);
 		let function = binding.get(name);
 		function.map(|x| x.clone()).or_else(|| {
-			RefCell::borrow(&self.registry.borrow().runtime()).get_function(name)
+			Lock::borrow(&self.registry.borrow().runtime()).get_function(name)
 		})
 	}
 
 	fn set_function(&mut self, name: &str, function: Function) {
-		RefCell::borrow_mut(&self.local_functions).insert(String::from(name), function);
+		Lock::borrow_mut(&self.local_functions).insert(String::from(name), function);
 	}
 
 	fn delete_function(&mut self, name: &str) {
-		RefCell::borrow_mut(&self.local_functions).remove(name);
+		Lock::borrow_mut(&self.local_functions).remove(name);
 	}
 
 	fn parent(&self) -> Option<ScopeRef> {
@@ -235,11 +305,11 @@ impl Scope for CustomModule {
 }
 
 impl Module for CustomModule {
-	fn get_submodule(&self, name: &str) -> Option<Rc<RefCell<dyn Module>>> {
+	fn get_submodule(&self, name: &str) -> Option<Shared<Lock<dyn Module>>> {
 		self.submodules
 			.borrow()
 			.get(name)
-			.map(|rc| Rc::clone(rc) as MutRc<dyn Module>)
+			.map(|rc| Shared::clone(rc) as MutRc<dyn Module>)
 	}
 
 	fn has_pub_function(&self, name: &str) -> bool {
@@ -1,11 +1,16 @@
 use super::ModuleBuilder;
 
-mod collections;
+pub(crate) mod collections;
 mod runtime;
 mod strings;
+mod test;
+
+pub use runtime::builtin_signature;
+pub use test::TestRun;
 
 pub(super) fn construct(module: &mut ModuleBuilder) {
     module
         .submodule("runtime", runtime::construct)
-        .submodule("strings", strings::construct);
+        .submodule("strings", strings::construct)
+        .submodule("test", test::construct);
 }
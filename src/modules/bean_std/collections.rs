@@ -1,6 +1,6 @@
-use std::{any::Any, cell::RefCell, collections::{ HashMap, VecDeque }, mem, rc::Rc};
+use std::{any::Any, collections::{ HashMap, VecDeque }, mem};
 
-use crate::{arg_check, as_mut_type, as_type, data::Data, error::{Error, ErrorSource}, scope::{function::Function, Scope, ScopeRef}};
+use crate::{arg_check, as_mut_type, as_type, data::Data, error::{Error, ErrorSource}, scope::{function::Function, Scope, ScopeRef}, util::{Lock, Shared}};
 
 #[derive(Debug)]
 pub struct List {
@@ -22,26 +22,26 @@ impl List {
 
         make(
             "size",
-            Rc::new(|_a, _y, list: ScopeRef| {
+            Shared::new(|_a, _y, list: ScopeRef| {
                 Ok(Data::Number(
-                    as_type!(RefCell::borrow(&list) => List, 
+                    as_type!(Lock::borrow(&list) => List, 
 					"Tried to call fn size on a non-list scope.").items.len() as f64
                 ))
             })
         );
         make(
             "empty",
-            Rc::new(|_a, _y, list: ScopeRef| {
+            Shared::new(|_a, _y, list: ScopeRef| {
                 Ok(Data::Boolean(
-                    as_type!(RefCell::borrow(&list) => List, "Tried to call fn empty on a non-list scope.").items.is_empty()
+                    as_type!(Lock::borrow(&list) => List, "Tried to call fn empty on a non-list scope.").items.is_empty()
                 ))
             })
         );
         make(
             "has",
-            Rc::new(|args, _y, list: ScopeRef| {
+            Shared::new(|args, _y, list: ScopeRef| {
                 Ok(Data::Boolean(
-                    as_type!(RefCell::borrow(&list) => List, 
+                    as_type!(Lock::borrow(&list) => List, 
 						"Tried to call fn has on a non-list scope.").items.contains(
                         &args[0]
                     )
@@ -50,16 +50,16 @@ impl List {
         );
         make(
             "at",
-            Rc::new(|args, _y, list: ScopeRef| {
+            Shared::new(|args, _y, list: ScopeRef| {
                 arg_check!(&args[0] => Data::Number(i), "Expected a number, but instead got {}.", "list:at");
-                Ok(as_type!(RefCell::borrow(&list) => List, 
+                Ok(as_type!(Lock::borrow(&list) => List, 
 						"Tried to call fn has on a non-list scope.").items.get(*i as usize).cloned().unwrap_or(Data::None))
             })
         );
         make(
             "push",
-            Rc::new(|args, _y, list: ScopeRef| {
-                as_mut_type!(RefCell::borrow_mut(&list) => List,
+            Shared::new(|args, _y, list: ScopeRef| {
+                as_mut_type!(Lock::borrow_mut(&list) => List,
 						"Tried to call fn push on a non-list scope.").items.push_back(
                     args[0].clone()
                 );
@@ -68,11 +68,11 @@ impl List {
         );
         make(
             "concat",
-            Rc::new(|args, _y, list: ScopeRef| {
+            Shared::new(|args, _y, list: ScopeRef| {
                 arg_check!(&args[0] => Data::Scope(list2), "Expected scope, but instead got {}.", "list:concat");
-                as_mut_type!(RefCell::borrow_mut(&list) => List,
+                as_mut_type!(Lock::borrow_mut(&list) => List,
 						"Tried to call fn concat on a non-list scope.").items.append(
-                    &mut as_type!(RefCell::borrow(&list2) => List,
+                    &mut as_type!(Lock::borrow(&list2) => List,
 						"Tried to call fn concat with a non-list scope.").items.clone()
                 );
                 Ok(Data::None)
@@ -80,8 +80,8 @@ impl List {
         );
         make(
             "pop",
-            Rc::new(|_a, _y, list: ScopeRef| {
-                Ok(as_mut_type!(RefCell::borrow_mut(&list) => List,
+            Shared::new(|_a, _y, list: ScopeRef| {
+                Ok(as_mut_type!(Lock::borrow_mut(&list) => List,
 						"Tried to call fn pop on a non-list scope.").items
                     .pop_back()
                     .unwrap_or_default())
@@ -89,9 +89,9 @@ impl List {
         );
         make(
             "delete",
-            Rc::new(|args, _y, list: ScopeRef| {
+            Shared::new(|args, _y, list: ScopeRef| {
                 arg_check!(&args[0] => Data::Number(i), "Expected number, but instead got {}.", "list:delete");
-                Ok(as_mut_type!(RefCell::borrow_mut(&list) => List,
+                Ok(as_mut_type!(Lock::borrow_mut(&list) => List,
 						"Tried to call fn delete on a non-list scope.").items
                     .remove(*i as usize)
                     .unwrap_or_default())
@@ -99,9 +99,9 @@ impl List {
         );
         make(
             "insert",
-            Rc::new(|args, _y, list: ScopeRef| {
+            Shared::new(|args, _y, list: ScopeRef| {
                 arg_check!(&args[0] => Data::Number(i), "Expected number, but instead got {}.", "list:insert");
-                as_mut_type!(RefCell::borrow_mut(&list) => List,
+                as_mut_type!(Lock::borrow_mut(&list) => List,
 						"Tried to call fn insert on a non-list scope.").items.insert(
                     *i as usize,
                     args[1].clone()
@@ -111,11 +111,11 @@ impl List {
         );
         make(
             "set",
-            Rc::new(|mut args, _y, list: ScopeRef| {
+            Shared::new(|mut args, _y, list: ScopeRef| {
                 arg_check!(&args[0] => Data::Number(i), "Expected number, but instead got {}.", "list:insert");
                 Ok(
                     mem::replace(
-                        as_mut_type!(RefCell::borrow_mut(&list) => List,
+                        as_mut_type!(Lock::borrow_mut(&list) => List,
                             "Tried to call fn set on a non-list scope.").items.get_mut(*i as usize)
                         .ok_or(Error::new("Index not inside list bounds.", ErrorSource::Builtin(String::from("list"))))?,
                         args.remove(1)
@@ -126,43 +126,33 @@ impl List {
 
         make(
             "for",
-            Rc::new(|args, body_fn, list: ScopeRef| {
+            Shared::new(|args, body_fn, list: ScopeRef| {
                 let body_fn = body_fn.expect("Expected body block for fn for.");
-                arg_check!(&args[0] => Data::Name { scope: item_scope_ref, name: item_name },
+                arg_check!(&args[0] => Data::Name { scope: _, name: item_name },
 					"Expected name, but instead got {}.",
 					"list:for");
 
-                let mut index_scope_ref: Option<&ScopeRef> = None;
                 let mut index_name: Option<&String> = None;
                 if args.len() > 1 {
-                    arg_check!(&args[1] => Data::Name { scope: i_scope_ref, name: i_name },
+                    arg_check!(&args[1] => Data::Name { scope: _, name: i_name },
 						"Expected name, but instead got {}.", "list:for");
-                    index_scope_ref = Some(i_scope_ref);
                     index_name = Some(i_name);
                 }
 
                 let mut mapped: Vec<Data> = Vec::new();
+                let items = as_type!(Lock::borrow(&list) => List,
+						"Tried to call fn for on a non-list scope.").items.clone();
 
-                for (
-                    i,
-                    item,
-                ) in &mut as_mut_type!(list.borrow_mut() => List,
-						"Tried to call fn for on a non-list scope.").items
-                    .iter()
-                    .enumerate() {
-                    item_scope_ref.borrow_mut().set_function(&item_name, Function::Constant {
-                        value: item.clone(),
-                    });
-                    index_scope_ref.map(|s|
-                        s.borrow_mut().set_function(&index_name.unwrap(), Function::Constant {
-                            value: Data::Number(i as f64),
-                        })
-                    );
-
-                    mapped.push(body_fn.call(Vec::new(), None, Rc::clone(&list))?);
+                for (i, item) in items.into_iter().enumerate() {
+                    let mut bindings = vec![(item_name.as_str(), item)];
+                    if let Some(index_name) = index_name {
+                        bindings.push((index_name.as_str(), Data::Number(i as f64)));
+                    }
+
+                    mapped.push(body_fn.with_bindings(bindings).call(Vec::new(), None, Shared::clone(&list))?);
                 }
 
-                Ok(Data::Scope(Rc::new(RefCell::new(List::new(mapped, None)))))
+                Ok(Data::Scope(Shared::new(Lock::new(List::new(mapped, None)))))
             })
         );
 
@@ -178,8 +168,8 @@ impl Scope for List {
     fn get_function(&self, name: &str) -> Option<Function> {
         if let Ok(i) = name.parse::<usize>() {
             Some(Function::BuiltIn {
-                callback: Rc::new(move |args, _y, scope: ScopeRef| {
-                    let mut binding = RefCell::borrow_mut(&scope);
+                callback: Shared::new(move |args, _y, scope: ScopeRef| {
+                    let mut binding = Lock::borrow_mut(&scope);
                     let list = as_mut_type!(binding => List, "Tried to index a non-list scope.");
                     if args.is_empty() {
                         Ok(list.items.get(i).cloned().unwrap_or_default())
@@ -203,7 +193,7 @@ impl Scope for List {
     }
 
     fn parent(&self) -> Option<ScopeRef> {
-        self.parent.as_ref().map(|x| Rc::clone(x))
+        self.parent.as_ref().map(|x| Shared::clone(x))
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -257,27 +247,27 @@ impl Map {
 
         make(
             "size",
-            Rc::new(|_a, _y, map: ScopeRef| {
+            Shared::new(|_a, _y, map: ScopeRef| {
                 Ok(Data::Number(
-                    as_type!(RefCell::borrow(&map) => Map, 
+                    as_type!(Lock::borrow(&map) => Map, 
 					"Tried to call fn size on a non-map scope.").hash.len() as f64
                 ))
             })
         );
         make(
             "empty",
-            Rc::new(|_a, _y, map: ScopeRef| {
+            Shared::new(|_a, _y, map: ScopeRef| {
                 Ok(Data::Boolean(
-                    as_type!(RefCell::borrow(&map) => Map, 
+                    as_type!(Lock::borrow(&map) => Map, 
 					"Tried to call fn size on a non-map scope.").hash.is_empty()
                 ))
             })
         );
         make(
             "has",
-            Rc::new(|args, _y, map: ScopeRef| {
+            Shared::new(|args, _y, map: ScopeRef| {
                 Ok(Data::Boolean(
-                    as_type!(RefCell::borrow(&map) => Map, "Tried to call fn has on a non-map scope.").hash.contains_key(
+                    as_type!(Lock::borrow(&map) => Map, "Tried to call fn has on a non-map scope.").hash.contains_key(
                         &args[0]
                     )
                 ))
@@ -285,8 +275,8 @@ impl Map {
         );
         make(
             "get",
-            Rc::new(|args, _y, map: ScopeRef| {
-                Ok(as_type!(RefCell::borrow(&map) => Map, "Tried to call fn has on a non-map scope.").hash
+            Shared::new(|args, _y, map: ScopeRef| {
+                Ok(as_type!(Lock::borrow(&map) => Map, "Tried to call fn has on a non-map scope.").hash
                     .get(&args[0])
                     .cloned()
                     .unwrap_or_default())
@@ -294,19 +284,19 @@ impl Map {
         );
         make(
             "set",
-            Rc::new(|args, body_fn, map: ScopeRef| {
+            Shared::new(|args, body_fn, map: ScopeRef| {
                 as_mut_type!(map.borrow_mut() => Map, "Tried to call fn set on a non-map scope.").hash.insert(
                     args[0].clone(),
                     body_fn
                         .expect("Expected body function for fn set.")
-                        .call(Vec::new(), None, Rc::clone(&map))?
+                        .call(Vec::new(), None, Shared::clone(&map))?
                 );
                 Ok(Data::None)
             })
         );
         make(
             "del",
-            Rc::new(|args, _y, map: ScopeRef| {
+            Shared::new(|args, _y, map: ScopeRef| {
                 as_mut_type!(map.borrow_mut() => Map, "Tried to call fn set on a non-map scope.").hash.remove(
                     &args[0].clone()
                 );
@@ -315,41 +305,37 @@ impl Map {
         );
         make(
             "for",
-            Rc::new(|args, body_fn, map: ScopeRef| {
+            Shared::new(|args, body_fn, map: ScopeRef| {
                 let body_fn = body_fn.expect("Expected body block for fn for.");
-                arg_check!(&args[0] => Data::Name { scope: key_scope_ref, name: key_name },
+                arg_check!(&args[0] => Data::Name { scope: _, name: key_name },
 					"Expected name for fn for, but instead got {}.", "map:for");
-                arg_check!(&args[1] => Data::Name { scope: value_scope_ref, name: value_name },
+                arg_check!(&args[1] => Data::Name { scope: _, name: value_name },
 					"Expected name for fn for, but instead got {}.", "map:for");
 
-                let mut index_scope_ref: Option<&ScopeRef> = None;
                 let mut index_name: Option<&String> = None;
                 if args.len() > 2 {
-                    arg_check!(&args[2] => Data::Name { scope: i_scope_ref, name: i_name },
+                    arg_check!(&args[2] => Data::Name { scope: _, name: i_name },
 						"Expected name for fn for, but instead got {}.", "map:for");
-                    index_scope_ref = Some(i_scope_ref);
                     index_name = Some(i_name);
                 }
 
                 let mut mapped: Vec<Data> = Vec::new();
-
-                for (
-                    i,
-                    (key, value),
-                ) in &mut as_mut_type!(map.borrow_mut() => Map,
-						"Tried to call fn for on a non-list scope.").hash
+                let entries = as_type!(Lock::borrow(&map) => Map,
+						"Tried to call fn for on a non-map scope.").hash
                     .iter()
-                    .enumerate() {
-                    key_scope_ref.borrow_mut().set_function(&key_name, Function::Constant { value: key.clone() });
-                    value_scope_ref.borrow_mut().set_function(&value_name, Function::Constant { value: value.clone() });
-                    index_scope_ref.map(|s|
-                        s.borrow_mut().set_function(&index_name.unwrap(), Function::Constant { value: Data::Number(i as f64) })
-                    );
-
-                    mapped.push(body_fn.call(Vec::new(), None, Rc::clone(&map))?);
+                    .map(|(key, value)| (key.clone(), value.clone()))
+                    .collect::<Vec<(Data, Data)>>();
+
+                for (i, (key, value)) in entries.into_iter().enumerate() {
+                    let mut bindings = vec![(key_name.as_str(), key), (value_name.as_str(), value)];
+                    if let Some(index_name) = index_name {
+                        bindings.push((index_name.as_str(), Data::Number(i as f64)));
+                    }
+
+                    mapped.push(body_fn.with_bindings(bindings).call(Vec::new(), None, Shared::clone(&map))?);
                 }
 
-                Ok(Data::Scope(Rc::new(RefCell::new(List::new(mapped, None)))))
+                Ok(Data::Scope(Shared::new(Lock::new(List::new(mapped, None)))))
             })
         );
 
@@ -370,8 +356,8 @@ impl Scope for Map {
         } else {
             let key = Data::String(String::from(name));
             Some(Function::BuiltIn {
-                callback: Rc::new(move |args, _y, scope: ScopeRef| {
-                    let mut binding = RefCell::borrow_mut(&scope);
+                callback: Shared::new(move |args, _y, scope: ScopeRef| {
+                    let mut binding = Lock::borrow_mut(&scope);
                     let map = as_mut_type!(binding => Map, "Tried to index a non-list scope.");
                     if args.is_empty() {
                         Ok(map.hash.get(&key).cloned().unwrap_or_default())
@@ -400,7 +386,7 @@ impl Scope for Map {
     }
 
     fn parent(&self) -> Option<ScopeRef> {
-        self.parent.as_ref().map(|s| Rc::clone(s))
+        self.parent.as_ref().map(|s| Shared::clone(s))
     }
 
     fn to_string(&self) -> String {
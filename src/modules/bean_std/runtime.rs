@@ -1,15 +1,16 @@
-use std::{cell::RefCell, rc::Rc, thread, time::Duration};
+use std::{thread, time::Duration};
 
 use crate::{
     arg_check, as_mut_type, as_type,
     data::{Data, DataType},
-    error::{Error, ErrorSource},
+    error::{BeanResult, Error, ErrorSource},
     modules::{loader, CustomModule, ModuleBuilder},
     scope::{
         block_scope::{BlockScope, IfState},
-        function::Function,
+        function::{parse_annotation, Function, Parameter, ReturnType, Signature},
         Scope, ScopeRef,
     },
+    util::{make_ref, Lock, Shared},
 };
 
 use super::collections::{List, Map};
@@ -17,91 +18,177 @@ use super::collections::{List, Map};
 pub fn construct(module: &mut ModuleBuilder) {
     /* NAME */
     module
-        .function("fn", fn_fn)
-        .function("let", fn_let)
-        .function("const", fn_const)
-        .function("del", fn_del)
-        .function("call", fn_call)
-        .function("exists", fn_exists);
+        .function_with_arity("fn", (1, None), fn_fn)
+        .function_with_arity("lambda", (0, None), fn_lambda)
+        .function_with_arity("let", (1, Some(1)), fn_let)
+        .function_with_arity("const", (1, Some(1)), fn_const)
+        .function_with_arity("del", (1, Some(1)), fn_del)
+        .function_with_arity("call", (1, None), fn_call)
+        .function_with_arity("exists", (1, Some(1)), fn_exists);
     if module.features.import {
-        module.function("export", fn_export).function("use", fn_use);
+        module
+            .function_with_arity("export", (1, Some(2)), fn_export)
+            .function_with_arity("use", (1, Some(2)), fn_use);
     }
 
     /* SCOPE */
     module
-        .function("p", fn_p)
-        .function("args", fn_args)
-        .function("body", fn_body)
-        .function("return", fn_return)
-        .function("pass", fn_pass)
-        .function("self", fn_self)
-        .function("super", fn_super)
-        .function("include", fn_include);
+        .function_with_arity("p", (1, Some(2)), fn_p)
+        .function_with_arity("args", (0, Some(0)), fn_args)
+        .function_with_arity("opt", (1, Some(2)), fn_opt)
+        .function_with_arity("rest", (1, Some(1)), fn_rest)
+        .function_with_arity("body", (0, None), fn_body)
+        .function_with_arity("return", (0, Some(1)), fn_return)
+        .function_with_arity("pass", (0, Some(1)), fn_pass)
+        .function_with_arity("self", (0, Some(0)), fn_self)
+        .function_with_arity("super", (0, Some(0)), fn_super)
+        .function_with_arity("include", (1, Some(1)), fn_include);
 
     /* INTERFACE */
     module
-        .function("print", fn_print)
-        .function("error", fn_error)
-        .function("sleep", fn_sleep);
+        .function_with_arity("print", (0, None), fn_print)
+        .function_with_arity("error", (1, Some(1)), fn_error)
+        .function_with_arity("sleep", (1, Some(1)), fn_sleep);
     if module.features.lang_debug {
-        module.function("__debug", fn_debug);
+        module.function_with_arity("__debug", (0, Some(0)), fn_debug);
     }
 
     /* MATH */
     module
-        .function("add", fn_add)
-        .function("+", fn_add)
-        .function("sub", fn_sub)
-        .function("-", fn_sub)
-        .function("mul", fn_mul)
-        .function("*", fn_mul)
-        .function("div", fn_div)
-        .function("/", fn_div)
-        .function("pow", fn_pow)
-        .function("^", fn_pow)
-        .function("rand", fn_rand)
-        .function("abs", fn_abs)
-        .function("sin", fn_sin)
-        .function("cos", fn_cos)
-        .function("tan", fn_tan)
-        .function("atan", fn_atan)
-        .function("sqrt", fn_sqrt)
-        .function("round", fn_round)
-        .function("floor", fn_floor)
-        .function("ceil", fn_ceil);
+        .function_with_arity("add", (1, None), fn_add)
+        .function_with_arity("+", (1, None), fn_add)
+        .function_with_arity("sub", (2, Some(2)), fn_sub)
+        .function_with_arity("-", (2, Some(2)), fn_sub)
+        .function_with_arity("mul", (2, Some(2)), fn_mul)
+        .function_with_arity("*", (2, Some(2)), fn_mul)
+        .function_with_arity("div", (2, Some(2)), fn_div)
+        .function_with_arity("/", (2, Some(2)), fn_div)
+        .function_with_arity("pow", (2, Some(2)), fn_pow)
+        .function_with_arity("^", (2, Some(2)), fn_pow)
+        .function_with_arity("rand", (0, Some(2)), fn_rand)
+        .function_with_arity("abs", (1, Some(1)), fn_abs)
+        .function_with_arity("sin", (1, Some(1)), fn_sin)
+        .function_with_arity("cos", (1, Some(1)), fn_cos)
+        .function_with_arity("tan", (1, Some(1)), fn_tan)
+        .function_with_arity("atan", (1, Some(1)), fn_atan)
+        .function_with_arity("sqrt", (1, Some(1)), fn_sqrt)
+        .function_with_arity("round", (1, Some(1)), fn_round)
+        .function_with_arity("floor", (1, Some(1)), fn_floor)
+        .function_with_arity("ceil", (1, Some(1)), fn_ceil);
 
     /* TYPES */
     module
-        .function("str", fn_str)
-        .function("num", fn_num)
-        .function("name", fn_name)
-        .function("type", fn_type);
+        .function_with_arity("str", (0, Some(1)), fn_str)
+        .function_with_arity("num", (0, Some(1)), fn_num)
+        .function_with_arity("name", (1, Some(1)), fn_name)
+        .function_with_arity("type", (0, Some(1)), fn_type);
 
     /* COLLECTIONS */
-    module.function("list", fn_list).function("map", fn_map);
+    module
+        .function_with_arity("list", (0, None), fn_list)
+        .function_with_arity("map", (0, None), fn_map);
 
     /* LOGIC */
     module
-        .function("eq", fn_eq)
-        .function("=", fn_eq)
-        .function("lt", fn_lt)
-        .function("gt", fn_gt)
-        .function("not", fn_not)
-        .function("!", fn_not)
-        .function("and", fn_and)
-        .function("&&", fn_and)
-        .function("or", fn_or)
-        .function("||", fn_or);
+        .function_with_arity("eq", (2, Some(2)), fn_eq)
+        .function_with_arity("=", (2, Some(2)), fn_eq)
+        .function_with_arity("lt", (2, Some(2)), fn_lt)
+        .function_with_arity("gt", (2, Some(2)), fn_gt)
+        .function_with_arity("not", (1, Some(1)), fn_not)
+        .function_with_arity("!", (1, Some(1)), fn_not)
+        .function_with_arity("and", (2, Some(2)), fn_and)
+        .function_with_arity("&&", (2, Some(2)), fn_and)
+        .function_with_arity("or", (2, Some(2)), fn_or)
+        .function_with_arity("||", (2, Some(2)), fn_or);
 
     /* CONTROL BLOCKS */
     module
-        .function("if", fn_if)
-        .function("else_if", fn_else_if)
-        .function("else", fn_else)
-        .function("ifv", fn_ifv)
-        .function("repeat", fn_repeat)
-        .function("while", fn_while)
-        .function("match", fn_match);
+        .function_with_arity("if", (1, Some(1)), fn_if)
+        .function_with_arity("else_if", (1, Some(1)), fn_else_if)
+        .function_with_arity("else", (0, Some(0)), fn_else)
+        .function_with_arity("ifv", (3, Some(3)), fn_ifv)
+        .function_with_arity("repeat", (1, Some(1)), fn_repeat)
+        .function_with_arity("while", (0, Some(0)), fn_while)
+        .function_with_arity("match", (1, Some(1)), fn_match);
+}
+
+/// Usage of each function registered by [`construct`], for editor tooling.
+pub fn builtin_signature(name: &str) -> Option<&'static str> {
+    Some(match name {
+        /* NAME */
+        "fn" => "fn(<name>, ...parameters): body",
+        "lambda" => "lambda(...parameters): body",
+        "let" => "let(<name>): value",
+        "const" => "const(<name>): value",
+        "del" => "del(<name>)",
+        "call" => "call(<name> | function, ...args): body",
+        "exists" => "exists(<name>): boolean",
+        "export" => "export(<name>, alias?)",
+        "use" => "use(path, <name>?): module",
+
+        /* SCOPE */
+        "p" => "p(index: number, type?: string)",
+        "args" => "args(): list",
+        "opt" => "opt(<name>, default?)",
+        "rest" => "rest(<name>)",
+        "body" => "body(...args)",
+        "return" => "return(value?)",
+        "pass" => "pass(value?)",
+        "self" => "self(): scope",
+        "super" => "super(): scope",
+        "include" => "include(scope: scope)",
+
+        /* INTERFACE */
+        "print" => "print(...values)",
+        "error" => "error(message: string)",
+        "sleep" => "sleep(ms: number)",
+        "__debug" => "__debug()",
+
+        /* MATH */
+        "add" | "+" => "add(value: number | string, ...values: number | string): number | string",
+        "sub" | "-" => "sub(a: number, b: number): number",
+        "mul" | "*" => "mul(a: number | string, b: number): number | string",
+        "div" | "/" => "div(a: number, b: number): number",
+        "pow" | "^" => "pow(a: number, b: number): number",
+        "rand" => "rand(min?: number, max?: number): number",
+        "abs" => "abs(n: number): number",
+        "sin" => "sin(n: number): number",
+        "cos" => "cos(n: number): number",
+        "tan" => "tan(n: number): number",
+        "atan" => "atan(n: number): number",
+        "sqrt" => "sqrt(n: number): number",
+        "round" => "round(n: number): number",
+        "floor" => "floor(n: number): number",
+        "ceil" => "ceil(n: number): number",
+
+        /* TYPES */
+        "str" => "str(value?): string",
+        "num" => "num(value?): number",
+        "name" => "name(string: string): name",
+        "type" => "type(value?): string",
+
+        /* COLLECTIONS */
+        "list" => "list(...items): list",
+        "map" => "map(...entries): map",
+
+        /* LOGIC */
+        "eq" | "=" => "eq(a, b): boolean",
+        "lt" => "lt(a: number, b: number): boolean",
+        "gt" => "gt(a: number, b: number): boolean",
+        "not" | "!" => "not(value: boolean): boolean",
+        "and" | "&&" => "and(a: boolean, b: boolean): boolean",
+        "or" | "||" => "or(a: boolean, b: boolean): boolean",
+
+        /* CONTROL BLOCKS */
+        "if" => "if(condition: boolean): body",
+        "else_if" => "else_if(condition: boolean): body",
+        "else" => "else(): body",
+        "ifv" => "ifv(condition: boolean, then, else)",
+        "repeat" => "repeat(times: number): body",
+        "while" => "while(): body",
+        "match" => "match(value): cases",
+        _ => return None,
+    })
 }
 
 //
@@ -111,25 +198,61 @@ pub fn construct(module: &mut ModuleBuilder) {
 fn fn_fn(args: Vec<Data>, body_fn: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Name { scope, name },
 		"Expected name as name of function, but instead got {}.", "function");
-    let body_fn = body_fn.unwrap_or_else(|| panic!("To define a function, add a body block."));
+    let (name, return_type) = parse_annotation(name)?;
+    let body_fn = match (body_fn, args.get(1)) {
+        (Some(mut body_fn), _) => {
+            // Functions without declared parameters keep reading their
+            // arguments with p(), even when they declare a return type.
+            if args.len() > 1 {
+                body_fn = body_fn
+                    .with_signature(Signature::new(&name, &args[1..])?)
+                    .trace(ErrorSource::Builtin(String::from("function")))?;
+            }
+            if return_type != DataType::Any {
+                body_fn = body_fn
+                    .with_return_type(ReturnType {
+                        name: name.clone(),
+                        return_type,
+                    })
+                    .trace(ErrorSource::Builtin(String::from("function")))?;
+            }
+            body_fn
+        }
+        (None, Some(Data::Function(function))) => *function.clone(),
+        _ => panic!("To define a function, add a body block."),
+    };
 
-    RefCell::borrow_mut(&scope).set_function(name, body_fn);
+    Lock::borrow_mut(scope).set_function(&name, body_fn);
 
     Ok(Data::None)
 }
 
+fn fn_lambda(args: Vec<Data>, body_fn: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+    let mut body_fn = body_fn.ok_or(Error::new(
+        "To create a lambda, add a body block.",
+        ErrorSource::Builtin(String::from("lambda")),
+    ))?;
+    if !args.is_empty() {
+        body_fn = body_fn
+            .with_signature(Signature::new("lambda", &args)?)
+            .trace(ErrorSource::Builtin(String::from("lambda")))?;
+    }
+
+    Ok(Data::Function(Box::new(body_fn)))
+}
+
 fn fn_let(args: Vec<Data>, body_fn: Option<Function>, o_scope: ScopeRef) -> Result<Data, Error> {
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Name { scope, name },
 		"Expected name as name of variable, but instead got {}.", "let");
     let value = body_fn
         .unwrap_or_else(|| panic!("To define a variable, add a body block."))
-        .call_scope(Vec::new(), None, Rc::clone(&o_scope))?;
+        .call_scope(Vec::new(), None, Shared::clone(&o_scope))?;
 
-    RefCell::borrow_mut(scope).set_function(
+    Lock::borrow_mut(scope).set_function(
         name,
         Function::Variable {
             value,
-            scope_ref: Rc::clone(scope),
+            scope_ref: Shared::clone(scope),
             name: String::from(name),
         },
     );
@@ -142,9 +265,9 @@ fn fn_const(args: Vec<Data>, body_fn: Option<Function>, o_scope: ScopeRef) -> Re
 		"Expected name as name of constant, but instead got {}.", "constant");
     let value = body_fn
         .unwrap_or_else(|| panic!("To define a constant, add a body block."))
-        .call_scope(Vec::new(), None, Rc::clone(&o_scope))?;
+        .call_scope(Vec::new(), None, Shared::clone(&o_scope))?;
 
-    RefCell::borrow_mut(scope).set_function(name, Function::Constant { value });
+    Lock::borrow_mut(scope).set_function(name, Function::Constant { value });
 
     Ok(Data::None)
 }
@@ -152,14 +275,18 @@ fn fn_const(args: Vec<Data>, body_fn: Option<Function>, o_scope: ScopeRef) -> Re
 fn fn_del(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Name { scope, name },
 		"Expected name to delete, but instead got {}.", "delete");
-    RefCell::borrow_mut(scope).delete_function(name);
+    Lock::borrow_mut(scope).delete_function(name);
 
     Ok(Data::None)
 }
 
 fn fn_call(args: Vec<Data>, body_fn: Option<Function>, o_scope: ScopeRef) -> Result<Data, Error> {
+    if let Some(Data::Function(function)) = args.first() {
+        return function.call(args[1..].to_vec(), body_fn, o_scope);
+    }
+
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Name { scope, name },
-		"Expected name to call, but instead got {}.", "call");
+		"Expected name or function to call, but instead got {}.", "call");
     let function = scope.borrow().get_function(name);
     if let None = function {
         return Err(Error::new(
@@ -178,7 +305,7 @@ fn fn_exists(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data
 }
 
 fn fn_export(args: Vec<Data>, _y: Option<Function>, to_scope: ScopeRef) -> Result<Data, Error> {
-    let mut binding = RefCell::borrow_mut(&to_scope);
+    let mut binding = Lock::borrow_mut(&to_scope);
     let module = as_mut_type!(binding => CustomModule, "Tried to export from a non-module scope.");
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Name { scope, name }, "Expected name, but instead got {}.", "export");
 
@@ -242,7 +369,7 @@ fn fn_use(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data
         match args.get(1).unwrap_or(&Data::None) {
             Data::Name { name, scope } => {
                 name_str = name as &str;
-                name_scope = Rc::clone(scope);
+                name_scope = Shared::clone(scope);
             }
             _ => panic!(
                 "Expected name use, but instead got {}.",
@@ -250,16 +377,16 @@ fn fn_use(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data
             ),
         }
     } else {
-        name_scope = Rc::clone(&scope);
+        name_scope = Shared::clone(&scope);
         name_str = target.unwrap_or(*path.last().unwrap());
     }
 
     let module = loader::get(file_module, String::from(path_str))?;
 
     if target == Some("*") {
-        let mut scope = RefCell::borrow_mut(&scope);
+        let mut scope = Lock::borrow_mut(&scope);
 
-        for (name, func) in RefCell::borrow(&module).get_function_list() {
+        for (name, func) in Lock::borrow(&module).get_function_list() {
             scope.set_function(&name, func.clone());
         }
 
@@ -324,7 +451,7 @@ fn fn_p(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data,
 }
 
 fn fn_args(_a: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    Ok(Data::Scope(Rc::new(RefCell::new(List::new(
+    Ok(Data::Scope(Shared::new(Lock::new(List::new(
         Vec::clone(
             &scope
                 .borrow()
@@ -337,12 +464,29 @@ fn fn_args(_a: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data,
     )))))
 }
 
+fn fn_opt(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Name { name, .. }, "Expected name of parameter, but instead got {}.", "optional_parameter");
+    let mut parameter = Parameter::new(name, args.get(1).cloned(), false)
+        .trace(ErrorSource::Builtin(String::from("optional_parameter")))?;
+    // Without a default, an omitted argument is none whatever the type.
+    parameter.default.get_or_insert(Data::None);
+    Ok(Data::Scope(make_ref(parameter)))
+}
+
+fn fn_rest(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Name { name, .. }, "Expected name of parameter, but instead got {}.", "rest_parameter");
+    Ok(Data::Scope(make_ref(
+        Parameter::new(name, None, true)
+            .trace(ErrorSource::Builtin(String::from("rest_parameter")))?,
+    )))
+}
+
 fn fn_body(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
     let call_scope = &scope
         .borrow()
         .get_call_scope()
         .expect("Cannot call fn body outside a call scope.");
-    let call_scope = RefCell::borrow(&call_scope);
+    let call_scope = Lock::borrow(&call_scope);
     Option::as_ref(call_scope.body_fn().as_ref())
         .expect("Expected body function.")
         .call_direct(args, body_fn, call_scope.from_scope())
@@ -350,8 +494,8 @@ fn fn_body(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Resul
 
 fn fn_return(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
     let value = args.get(0).cloned().unwrap_or(Data::None);
-    RefCell::borrow_mut(&scope).set_return_value(value.clone());
-    match RefCell::borrow_mut(&scope)
+    Lock::borrow_mut(&scope).set_return_value(value.clone());
+    match Lock::borrow_mut(&scope)
         .as_mut()
         .downcast_mut::<BlockScope>()
     {
@@ -363,26 +507,26 @@ fn fn_return(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<D
 
 fn fn_pass(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
     let value = args.get(0).cloned().unwrap_or(Data::None);
-    RefCell::borrow_mut(&scope).set_return_value(value.clone());
+    Lock::borrow_mut(&scope).set_return_value(value.clone());
     Ok(value)
 }
 
 fn fn_self(_a: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    Ok(Data::Scope(Rc::clone(&scope)))
+    Ok(Data::Scope(Shared::clone(&scope)))
 }
 
 fn fn_super(_a: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    Ok(RefCell::borrow(&scope)
+    Ok(Lock::borrow(&scope)
         .parent()
-        .map(|s| Data::Scope(Rc::clone(&s)))
+        .map(|s| Data::Scope(Shared::clone(&s)))
         .unwrap_or(Data::None))
 }
 
 fn fn_include(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
     arg_check!(args.get(0).unwrap_or(&Data::None) => Data::Scope(target), "Expected scope, but instead got {}.", "include");
-    let mut scope = RefCell::borrow_mut(&scope);
+    let mut scope = Lock::borrow_mut(&scope);
 
-    for (name, func) in RefCell::borrow(&target).get_function_list() {
+    for (name, func) in Lock::borrow(&target).get_function_list() {
         scope.set_function(&name, func.clone());
     }
 
@@ -393,12 +537,23 @@ fn fn_include(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<
 // INTERFACE
 //
 
-fn fn_print(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+fn fn_print(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
     let mut string = Vec::new();
     for data in args {
         string.push(data.to_string());
     }
-    println!("{}", string.join(" "));
+
+    let module = scope.borrow().get_file_module();
+    let registry = module.as_ref().and_then(|module| {
+        Lock::borrow(module)
+            .as_any()
+            .downcast_ref::<CustomModule>()
+            .map(|module| Shared::clone(&module.registry))
+    });
+    match registry {
+        Some(registry) => registry.borrow_mut().logger.log(string.join(" ")),
+        None => println!("{}", string.join(" ")),
+    }
 
     Ok(Data::None)
 }
@@ -554,6 +709,8 @@ fn fn_num(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, E
         Data::String(s) => Ok(s.parse().map(|v| Data::Number(v)).unwrap_or(Data::None)),
         Data::Name { .. } => Ok(Data::None),
         Data::Scope(_) => Ok(Data::None),
+        Data::Function(_) => Ok(Data::None),
+        Data::UserData(_) => Ok(Data::None),
         Data::None => Ok(Data::None),
     }
 }
@@ -576,14 +733,14 @@ fn fn_type(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data,
 //
 
 fn fn_list(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    Ok(Data::Scope(Rc::new(RefCell::new(List::new(
+    Ok(Data::Scope(Shared::new(Lock::new(List::new(
         args,
         Some(scope),
     )))))
 }
 
 fn fn_map(args: Vec<Data>, _y: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    Ok(Data::Scope(Rc::new(RefCell::new(Map::new(
+    Ok(Data::Scope(Shared::new(Lock::new(Map::new(
         args,
         Some(scope),
     )))))
@@ -638,7 +795,7 @@ fn fn_if(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<
     let state: IfState = if *v {
         body_fn
             .expect("Expected body block for if statement")
-            .call_direct(Vec::new(), None, Rc::clone(&scope))?;
+            .call_direct(Vec::new(), None, Shared::clone(&scope))?;
         IfState::Captured
     } else {
         IfState::Started
@@ -663,7 +820,7 @@ fn fn_else_if(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Re
                 scope.borrow_mut().set_if_state(IfState::Captured);
                 body_fn
                     .unwrap_or_else(|| panic!("To define a variable, add a body block."))
-                    .call_direct(Vec::new(), None, Rc::clone(&scope))?;
+                    .call_direct(Vec::new(), None, Shared::clone(&scope))?;
             } else {
                 scope.borrow_mut().set_if_state(IfState::Started);
             }
@@ -688,7 +845,7 @@ fn fn_else(_a: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<
             scope.borrow_mut().set_if_state(IfState::Finished);
             body_fn
                 .unwrap_or_else(|| panic!("To define a variable, add a body block."))
-                .call_direct(Vec::new(), None, Rc::clone(&scope))?;
+                .call_direct(Vec::new(), None, Shared::clone(&scope))?;
             Ok(Data::None)
         }
         IfState::Captured => {
@@ -720,7 +877,7 @@ fn fn_repeat(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Res
     ))?;
 
     for _ in 0..*n as usize {
-        let v = body_fn.call_direct(Vec::new(), None, Rc::clone(&scope))?;
+        let v = body_fn.call_direct(Vec::new(), None, Shared::clone(&scope))?;
         if Data::Boolean(false) == v {
             break;
         }
@@ -736,7 +893,7 @@ fn fn_while(_a: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result
     ))?;
 
     loop {
-        let v = body_fn.call_direct(Vec::new(), None, Rc::clone(&scope))?;
+        let v = body_fn.call_direct(Vec::new(), None, Shared::clone(&scope))?;
         if Data::Boolean(false) == v {
             break;
         }
@@ -756,7 +913,7 @@ impl Scope for MatchScope {
         if name == "case" || name == "default" {
             true
         } else {
-            RefCell::borrow(&self.parent).has_function(name)
+            Lock::borrow(&self.parent).has_function(name)
         }
     }
 
@@ -764,13 +921,13 @@ impl Scope for MatchScope {
         if name == "case" {
             let match_value = self.value.clone();
             Some(Function::BuiltIn {
-                callback: Rc::new(move |args, body_fn, scope| {
+                callback: Shared::new(move |args, body_fn, scope| {
                     if match_value == args[0] {
-                        let mut scope_m = RefCell::borrow_mut(&scope);
+                        let mut scope_m = Lock::borrow_mut(&scope);
                         scope_m.set_return_value(
                             body_fn
                                 .expect("Expected body block for function case.")
-                                .call(Vec::new(), None, Rc::clone(&scope))?,
+                                .call(Vec::new(), None, Shared::clone(&scope))?,
                         );
                         as_mut_type!(scope_m => BlockScope, "Tried to call case in a non-block scope.").break_self();
                     }
@@ -779,36 +936,36 @@ impl Scope for MatchScope {
             })
         } else if name == "default" {
             Some(Function::BuiltIn {
-                callback: Rc::new(move |_a, body_fn, scope| {
-                    RefCell::borrow_mut(&scope).set_return_value(
+                callback: Shared::new(move |_a, body_fn, scope| {
+                    Lock::borrow_mut(&scope).set_return_value(
                         body_fn
                             .expect("Expected body block for function default.")
-                            .call(Vec::new(), None, Rc::clone(&scope))?,
+                            .call(Vec::new(), None, Shared::clone(&scope))?,
                     );
                     Ok(Data::None)
                 }),
             })
         } else {
-            RefCell::borrow(&self.parent).get_function(name)
+            Lock::borrow(&self.parent).get_function(name)
         }
     }
 
     fn set_function(&mut self, name: &str, function: Function) {
-        RefCell::borrow_mut(&self.parent).set_function(name, function)
+        Lock::borrow_mut(&self.parent).set_function(name, function)
     }
 
     fn delete_function(&mut self, name: &str) {
-        RefCell::borrow_mut(&self.parent).delete_function(name)
+        Lock::borrow_mut(&self.parent).delete_function(name)
     }
 
     fn parent(&self) -> Option<ScopeRef> {
-        Some(Rc::clone(&self.parent))
+        Some(Shared::clone(&self.parent))
     }
 
     fn set_return_value(&mut self, _value: Data) {}
 
     fn get_function_list(&self) -> std::collections::HashMap<String, Function> {
-        RefCell::borrow(&self.parent).get_function_list()
+        Lock::borrow(&self.parent).get_function_list()
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -823,14 +980,14 @@ impl Scope for MatchScope {
 }
 
 fn fn_match(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
-    let match_scope = Rc::new(RefCell::new(MatchScope {
-        parent: Rc::clone(&scope),
+    let match_scope = Shared::new(Lock::new(MatchScope {
+        parent: Shared::clone(&scope),
         value: args[0].clone(),
     }));
 
     body_fn.expect("Expected body for fn match").call_direct(
         Vec::new(),
         None,
-        Rc::clone(&match_scope) as ScopeRef,
+        Shared::clone(&match_scope) as ScopeRef,
     )
 }
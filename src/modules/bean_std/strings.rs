@@ -2,16 +2,15 @@ use crate::{
     arg_check,
     data::{Data, DataType},
     error::{Error, ErrorSource},
-    modules::{bean_std::collections::List, ModuleBuilder},
+    modules::ModuleBuilder,
     scope::{function::Function, ScopeRef},
-    util::make_ref,
 };
 
 pub(super) fn construct(module: &mut ModuleBuilder) {
     module
         .function("size", fn_size)
-        .function("split", fn_split)
-        .function("chars", fn_chars)
+        .typed_function("split", fn_split)
+        .typed_function("chars", fn_chars)
         .function("substr", fn_substr);
 }
 
@@ -20,17 +19,12 @@ fn fn_size(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data,
     Ok(Data::Number(s.len() as f64))
 }
 
-fn fn_split(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
-    arg_check!(args.get(0).unwrap_or(&Data::None) => Data::String(s), "Expected string, but got {} instead.", "std/string:split");
-    arg_check!(args.get(1).unwrap_or(&Data::None) => Data::String(d), "Expected delimiter string split, but got {} instead.", "split_string");
-    let vec = s.split(d).map(|c| Data::String(String::from(c))).collect();
-    Ok(Data::Scope(make_ref(List::new(vec, None))))
+fn fn_split(s: String, delimiter: String) -> Vec<String> {
+    s.split(&delimiter).map(String::from).collect()
 }
 
-fn fn_chars(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
-    arg_check!(args.get(0).unwrap_or(&Data::None) => Data::String(s), "Expected string, but got {} instead.", "std/string:chars");
-    let vec = s.chars().map(|c| Data::String(String::from(c))).collect();
-    Ok(Data::Scope(make_ref(List::new(vec, None))))
+fn fn_chars(s: String) -> Vec<String> {
+    s.chars().map(String::from).collect()
 }
 
 fn fn_substr(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
@@ -0,0 +1,141 @@
+use crate::{
+    arg_check,
+    data::Data,
+    error::{Error, ErrorSource},
+    modules::{
+        bean_std::collections::{List, Map},
+        registry::ModuleRegistry,
+        CustomModule, ModuleBuilder,
+    },
+    scope::{function::Function, ScopeRef},
+    util::{diff_lines, Lock, MutRc, Shared},
+};
+
+/// State of a test runner, stored in the registry metadata under
+/// [`TestRun::KEY`]. Without it, `test` runs every test body directly.
+#[derive(Debug, Default)]
+pub struct TestRun {
+    /// Index of the only test to run. When `None`, tests are only collected.
+    pub only: Option<usize>,
+    /// Names of the tests declared so far, in order.
+    pub names: Vec<String>,
+    /// Result of the test that ran.
+    pub outcome: Option<Result<(), Error>>,
+}
+
+impl TestRun {
+    pub const KEY: &'static str = "std/test";
+}
+
+pub(super) fn construct(module: &mut ModuleBuilder) {
+    module
+        .function("assert", fn_assert)
+        .function("assert_eq", fn_assert_eq)
+        .function("test", fn_test);
+}
+
+fn registry(scope: &ScopeRef) -> Option<MutRc<ModuleRegistry>> {
+    let module = scope.borrow().get_file_module()?;
+    let module = Lock::borrow(&module);
+    module
+        .as_any()
+        .downcast_ref::<CustomModule>()
+        .map(|module| Shared::clone(&module.registry))
+}
+
+fn failure(message: Option<&Data>, default: String) -> Error {
+    let message = match message {
+        Some(Data::String(message)) => format!("{}\n{}", message, default),
+        _ => default,
+    };
+    Error::new(&message, ErrorSource::Builtin(String::from("std/test")))
+}
+
+fn fn_assert(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::Boolean(condition), "Expected boolean for assertion, but got {} instead.", "std/test:assert");
+    if *condition {
+        Ok(Data::None)
+    } else {
+        Err(failure(args.get(1), String::from("Assertion failed.")))
+    }
+}
+
+/// Compares lists and maps by their contents, and other values with `==`,
+/// which compares scopes by identity.
+fn values_equal(a: &Data, b: &Data) -> bool {
+    let (Data::Scope(a_ref), Data::Scope(b_ref)) = (a, b) else {
+        return a == b;
+    };
+    if Shared::ptr_eq(a_ref, b_ref) {
+        return true;
+    }
+
+    let (a_scope, b_scope) = (Lock::borrow(a_ref), Lock::borrow(b_ref));
+    let (a_any, b_any) = (a_scope.as_any(), b_scope.as_any());
+    if let (Some(a), Some(b)) = (a_any.downcast_ref::<List>(), b_any.downcast_ref::<List>()) {
+        a.items.len() == b.items.len()
+            && a.items.iter().zip(&b.items).all(|(a, b)| values_equal(a, b))
+    } else if let (Some(a), Some(b)) = (a_any.downcast_ref::<Map>(), b_any.downcast_ref::<Map>()) {
+        a.hash.len() == b.hash.len()
+            && a.hash
+                .iter()
+                .all(|(key, a)| b.hash.get(key).is_some_and(|b| values_equal(a, b)))
+    } else {
+        false
+    }
+}
+
+fn fn_assert_eq(args: Vec<Data>, _y: Option<Function>, _s: ScopeRef) -> Result<Data, Error> {
+    let actual = args.first().unwrap_or(&Data::None);
+    let expected = args.get(1).unwrap_or(&Data::None);
+    if values_equal(actual, expected) {
+        Ok(Data::None)
+    } else {
+        Err(failure(
+            args.get(2),
+            format!(
+                "Values are not equal.\n{}",
+                diff_lines(&expected.to_string(), &actual.to_string()).trim_end()
+            ),
+        ))
+    }
+}
+
+fn fn_test(args: Vec<Data>, body_fn: Option<Function>, scope: ScopeRef) -> Result<Data, Error> {
+    arg_check!(args.first().unwrap_or(&Data::None) => Data::String(name), "Expected string for test name, but got {} instead.", "std/test:test");
+    let Some(body_fn) = body_fn else {
+        return Err(Error::new(
+            "Expected body for test.",
+            ErrorSource::Builtin(String::from("std/test:test")),
+        ));
+    };
+
+    let registry = registry(&scope);
+    let selected = registry.as_ref().and_then(|registry| {
+        let mut registry = registry.borrow_mut();
+        let run = registry.metadata.get_mut(TestRun::KEY)?.downcast_mut::<TestRun>()?;
+        let index = run.names.len();
+        run.names.push(name.clone());
+        Some(run.only == Some(index))
+    });
+
+    match selected {
+        None => {
+            body_fn.call_direct(Vec::new(), None, scope)?;
+        }
+        Some(true) => {
+            let outcome = body_fn.call_direct(Vec::new(), None, scope).map(|_| ());
+            let mut registry = registry.as_ref().unwrap().borrow_mut();
+            if let Some(run) = registry
+                .metadata
+                .get_mut(TestRun::KEY)
+                .and_then(|run| run.downcast_mut::<TestRun>())
+            {
+                run.outcome = Some(outcome);
+            }
+        }
+        Some(false) => (),
+    }
+
+    Ok(Data::None)
+}
@@ -1,12 +1,12 @@
 use core::panic;
-use std::{any::Any, collections::HashMap, fs, path::PathBuf, rc::Rc};
+use std::{any::Any, collections::HashMap, path::PathBuf};
 
 use crate::{
     data::Data,
-    error::{BeanResult, Error, ErrorSource},
-    evaluator, lexer, parser,
+    error::{Error, ErrorSource},
+    hook,
     scope::{function::Function, Scope},
-    util::{make_ref, MutRc},
+    util::{make_ref, MutRc, Shared},
 };
 
 use super::{
@@ -52,22 +52,55 @@ impl Scope for ModuleWrapper {
 
 pub fn get(module: &CustomModule, path: String) -> Result<MutRc<ModuleWrapper>, Error> {
     let registry = module.registry.clone();
+    let mut tried = Vec::new();
 
     if path.starts_with("./") {
         let mut path_buf = module.file_path.clone();
         path_buf.push(path.clone().trim_start_matches("./"));
-        path_buf.set_extension("bean");
 
-        get_local(Rc::clone(&registry), path_buf).map(|m| make_ref(ModuleWrapper(m)))
+        if let Some(file) = find_file(path_buf, &mut tried) {
+            return get_local(Shared::clone(&registry), file).map(|m| make_ref(ModuleWrapper(m)));
+        }
     } else {
-        get_reg(&mut registry.borrow_mut().registered, path.clone()).map_or(
-            Err(Error::new(
-                &format!("Module {} does not exist.", path),
-                ErrorSource::Internal,
-            )),
-            |s| Ok(make_ref(ModuleWrapper(s))),
-        )
+        let search_paths = registry.borrow().search_paths.clone();
+        for root in search_paths {
+            if let Some(file) = find_file(root.join(&path), &mut tried) {
+                return get_local(Shared::clone(&registry), file)
+                    .map(|m| make_ref(ModuleWrapper(m)));
+            }
+        }
+
+        let registered = get_reg(&mut registry.borrow_mut().registered, path.clone());
+        if let Some(module) = registered {
+            return Ok(make_ref(ModuleWrapper(module)));
+        }
+    }
+
+    let mut message = format!("Module {} does not exist.", path);
+    if !tried.is_empty() {
+        message.push_str(" Tried:");
+        for file in tried {
+            message.push_str(&format!("\n\t{}", file.display()));
+        }
+    } else if !path.starts_with("./") {
+        message.push_str(
+            " No module search paths are set. Add directories to BEAN_PATH, or pass them with -I.",
+        );
     }
+    Err(Error::new(&message, ErrorSource::Internal))
+}
+
+/// The source or compiled file for a module path without an extension,
+/// recording the files that were looked for.
+fn find_file(path: PathBuf, tried: &mut Vec<PathBuf>) -> Option<PathBuf> {
+    for extension in ["bean", "beanc"] {
+        let file = path.with_extension(extension);
+        if file.exists() {
+            return Some(file);
+        }
+        tried.push(file);
+    }
+    None
 }
 
 fn get_reg(
@@ -82,7 +115,7 @@ fn get_reg(
     }
 
     let mut module = registered.get(path[0]).map_or(None, |x| match x {
-        RegistryEntry::Available(r) => Some(Rc::clone(r)),
+        RegistryEntry::Available(r) => Some(Shared::clone(r)),
         RegistryEntry::Uninitialized(_) => None,
     });
 
@@ -111,30 +144,132 @@ fn get_local(registry: MutRc<ModuleRegistry>, path: PathBuf) -> Result<MutRc<Cus
     }
     let exists = registry.borrow().local.get(&path).is_none();
     if exists {
-        let file = fs::read_to_string(path.clone()).map_err(|e| {
-            Error::new(
-                &(String::from("Error reading file ")
-                    + path.to_str().unwrap_or("")
-                    + ": "
-                    + &e.to_string()),
-                ErrorSource::Internal,
-            )
-        })?;
+        let scripts = registry.borrow().scripts.clone();
+        let script = scripts.get_or_compile(&path)?;
+        hook::module_load(&registry, &path, script.tree());
 
-        let tokens = lexer::tokenize(file);
-        let tree = parser::parse(tokens)?;
-
-        let module = CustomModule::new(Rc::clone(&registry), path.clone());
+        let module = CustomModule::new(Shared::clone(&registry), path.clone());
         let module_ref = make_ref(module);
         registry.borrow_mut().loading.push(path.clone());
 
-        evaluator::evaluate(&tree, CustomModule::to_scope(Rc::clone(&module_ref)))
-            .trace(ErrorSource::File(path.to_str().unwrap().to_string()))?;
+        script.run(CustomModule::to_scope(Shared::clone(&module_ref)))?;
 
         let mut registry_mut = registry.borrow_mut();
         registry_mut.local.insert(path.clone(), module_ref);
         registry_mut.loading.pop();
     }
 
-    Ok(Rc::clone(registry.borrow().local.get(&path).unwrap()))
+    Ok(Shared::clone(registry.borrow().local.get(&path).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{compiled::CompiledScript, modules::registry::RegistryFeatures};
+
+    /// An empty directory under the system temp dir, unique to a test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bean-loader-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn module(search_paths: Vec<PathBuf>) -> CustomModule {
+        let mut registry = ModuleRegistry::new(RegistryFeatures::default());
+        registry.search_paths = search_paths;
+        CustomModule::new(make_ref(registry), PathBuf::new())
+    }
+
+    fn loaded(module: &CustomModule) -> Vec<PathBuf> {
+        module.registry.borrow().local.keys().cloned().collect()
+    }
+
+    #[test]
+    fn searches_paths_in_order_before_registered_modules() {
+        let dir = temp_dir("order");
+        for root in ["a", "b"] {
+            fs::create_dir(dir.join(root)).unwrap();
+            fs::write(dir.join(root).join("shared.bean"), "").unwrap();
+        }
+        fs::write(dir.join("b").join("only_b.bean"), "").unwrap();
+        fs::write(dir.join("b").join("tools.bean"), "").unwrap();
+
+        let module = module(vec![dir.join("a"), dir.join("b")]);
+        module
+            .registry
+            .borrow_mut()
+            .register_builtin(String::from("tools"), |_| {});
+
+        get(&module, String::from("shared")).unwrap();
+        assert_eq!(loaded(&module), [dir.join("a").join("shared.bean")]);
+
+        get(&module, String::from("only_b")).unwrap();
+        assert!(loaded(&module).contains(&dir.join("b").join("only_b.bean")));
+
+        get(&module, String::from("tools")).unwrap();
+        assert!(loaded(&module).contains(&dir.join("b").join("tools.bean")));
+
+        // Registered modules are still found when no file matches.
+        get(&module, String::from("std")).unwrap();
+        assert_eq!(loaded(&module).len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn finds_compiled_modules() {
+        let dir = temp_dir("compiled");
+        let source = dir.join("lib.bean");
+        fs::write(&source, "fn(<answer>): 42\nexport(<answer>)").unwrap();
+        let bytes = CompiledScript::compile_file(&source)
+            .and_then(|script| script.to_bytes())
+            .unwrap();
+        fs::write(dir.join("lib.beanc"), bytes).unwrap();
+        fs::remove_file(&source).unwrap();
+
+        let module = module(vec![dir.clone()]);
+        let lib = get(&module, String::from("lib")).unwrap();
+        assert!(lib.borrow().has_function("answer"));
+        assert_eq!(loaded(&module), [dir.join("lib.beanc")]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lists_the_files_tried() {
+        let dir = temp_dir("tried");
+        let importer = module(vec![dir.join("a"), dir.join("b")]);
+
+        let error = get(&importer, String::from("utils/strings")).unwrap_err();
+        let tried: Vec<String> = ["a", "b"]
+            .into_iter()
+            .flat_map(|root| {
+                ["bean", "beanc"]
+                    .map(|ext| dir.join(root).join("utils/strings").with_extension(ext))
+            })
+            .map(|file| format!("\n\t{}", file.display()))
+            .collect();
+        assert_eq!(
+            error.message(),
+            format!(
+                "Module utils/strings does not exist. Tried:{}",
+                tried.concat()
+            )
+        );
+
+        let error = get(&importer, String::from("./missing")).unwrap_err();
+        assert!(error
+            .message()
+            .starts_with("Module ./missing does not exist. Tried:"));
+
+        let error = get(&module(Vec::new()), String::from("missing")).unwrap_err();
+        assert!(error
+            .message()
+            .ends_with("Add directories to BEAN_PATH, or pass them with -I."));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
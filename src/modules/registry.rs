@@ -1,14 +1,21 @@
-use std::{any::Any, cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc};
+use std::{collections::HashMap, path::PathBuf};
 
 use crate::{
+	compiled::ScriptCache,
+	hook::ExecutionHook,
 	logger::Logger,
-	util::{make_ref, MutRc},
+	util::{make_ref, DynAny, Lock, MutRc, Shared},
 };
 
 use super::{bean_std, BuiltinModule, CustomModule, Module, ModuleBuilder};
 
+#[cfg(not(feature = "sync"))]
+type ModuleInit = dyn FnOnce() -> MutRc<dyn Module>;
+#[cfg(feature = "sync")]
+type ModuleInit = dyn FnOnce() -> MutRc<dyn Module> + Send + Sync;
+
 pub(super) enum RegistryEntry {
-	Uninitialized(Box<dyn FnOnce() -> MutRc<dyn Module>>),
+	Uninitialized(Box<ModuleInit>),
 	Available(MutRc<dyn Module>),
 }
 
@@ -42,10 +49,20 @@ pub struct ModuleRegistry {
 	pub(super) registered: HashMap<String, RegistryEntry>,
 	pub(super) local: HashMap<PathBuf, MutRc<CustomModule>>,
 	pub(super) loading: Vec<PathBuf>,
+	/// Compiled files used when loading local modules. Registries can share
+	/// a cache by cloning it.
+	pub scripts: ScriptCache,
+	/// Directories searched, in order, for modules that are not relative, so
+	/// `use("utils/strings")` can load `<root>/utils/strings.bean`. They are
+	/// searched before registered modules. Empty unless set by the embedder;
+	/// `beans` fills it from `-I` and `BEAN_PATH`.
+	pub search_paths: Vec<PathBuf>,
 	runtime: MutRc<BuiltinModule>,
 	pub features: RegistryFeatures,
 	pub logger: Logger,
-	pub metadata: HashMap<String, Box<dyn Any>>,
+	pub hooks: Vec<MutRc<dyn ExecutionHook>>,
+	pub(crate) unwinding: bool,
+	pub metadata: HashMap<String, Box<DynAny>>,
 }
 
 impl std::fmt::Debug for ModuleRegistry {
@@ -61,17 +78,21 @@ impl ModuleRegistry {
 			registered: HashMap::new(),
 			local: HashMap::new(),
 			loading: Vec::new(),
-			runtime: match RefCell::borrow(
+			scripts: ScriptCache::new(),
+			search_paths: Vec::new(),
+			runtime: match Lock::borrow(
 				&standard_lib.get_submodule("runtime").unwrap(),
 			)
 			.as_any()
 			.downcast_ref::<BuiltinModule>()
 			{
-				Some(r) => Rc::new(RefCell::new(r.clone())),
+				Some(r) => Shared::new(Lock::new(r.clone())),
 				None => panic!("Runtime module is custom?"),
 			},
-			features: RegistryFeatures::default(),
+			features,
 			logger: Logger::Stdout,
+			hooks: Vec::new(),
+			unwinding: false,
 			metadata: HashMap::new(),
 		};
 		s.registered.insert(
@@ -113,6 +134,6 @@ impl ModuleRegistry {
 	}
 
 	pub fn runtime(&self) -> MutRc<BuiltinModule> {
-		Rc::clone(&self.runtime)
+		Shared::clone(&self.runtime)
 	}
 }
@@ -11,6 +11,7 @@ pub enum Node {
 		name: String,
 		parameters: Vec<Box<PosNode>>,
 		body_fn: Option<Box<PosNode>>,
+		doc: Option<String>,
 	},
 	Scope {
 		body: Vec<Box<PosNode>>,
@@ -30,12 +31,18 @@ pub enum Node {
 	String(String),
 	Name(String),
 	None,
+
+	Comment(String),
+	EmptyLine,
 }
 
 #[derive(Debug, Clone)]
 pub struct PosNode {
 	pub node: Node,
+	/// Line on which the node ends.
 	pub ln: usize,
+	/// Line on which the node starts.
+	pub start_ln: usize,
 }
 
 pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
@@ -45,6 +52,12 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 
 	let next = || &tokens[i.replace(i.get() + 1)];
 	let peek = || &tokens[i.get()];
+	let peek_past_breaks = || {
+		tokens[i.get()..]
+			.iter()
+			.find(|t| !matches!(t, Token::LineBreak))
+			.unwrap_or(&Token::EOF)
+	};
 	let get_ln = || line.get();
 	let new_ln = || line.replace(line.get() + 1);
 
@@ -52,6 +65,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 		mut token: &'a Token,
 		next: &dyn Fn() -> &'a Token,
 		peek: &dyn Fn() -> &'a Token,
+		peek_past_breaks: &dyn Fn() -> &'a Token,
 		get_ln: &dyn Fn() -> usize,
 		new_ln: &dyn Fn() -> usize,
 		prevent_accessor: bool,
@@ -60,6 +74,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 			new_ln();
 			token = next();
 		}
+		let start_ln = get_ln();
 
 		let node: Node = match token {
 			Token::FnName(name) => {
@@ -85,8 +100,14 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 						}
 
 						let mut body = Vec::new();
+						let parameter_ln = get_ln();
 
 						loop {
+							while let Token::LineBreak = peek() {
+								new_ln();
+								next();
+							}
+
 							match peek() {
 								Token::ArgSeparator | Token::ArgClose | Token::EOF => {
 									break;
@@ -97,6 +118,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 								next(),
 								&next,
 								&peek,
+								&peek_past_breaks,
 								&get_ln,
 								&new_ln,
 								false,
@@ -110,6 +132,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 						parameters.push(Box::new(PosNode {
 							node: Node::ParameterBlock { body },
 							ln: get_ln(),
+							start_ln: parameter_ln,
 						}));
 					}
 
@@ -118,44 +141,40 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 					}
 				}
 
-				while let Token::LineBreak = peek() {
-					new_ln();
-					next();
-				}
-
-				if let Token::FnBody = peek() {
+				if let Token::FnBody = peek_past_breaks() {
+					while let Token::LineBreak = peek() {
+						new_ln();
+						next();
+					}
 					next();
 					body_fn = Some(Box::new(parse_token(
 						next(),
 						&next,
 						&peek,
+						&peek_past_breaks,
 						&get_ln,
 						&new_ln,
 						false,
 					)?));
 				}
 
-				while let Token::LineBreak = peek() {
-					new_ln();
-					next();
-				}
-
-				if (if let Token::Accessor = peek() {
-					true
-				} else {
-					false
-				}) && !prevent_accessor
-				{
+				if matches!(peek_past_breaks(), Token::Accessor) && !prevent_accessor {
 					let mut node = PosNode {
 						node: Node::FnCall {
 							name: name.clone(),
 							parameters,
 							body_fn,
+							doc: None,
 						},
 						ln: get_ln(),
+						start_ln,
 					};
 
-					while let Token::Accessor = peek() {
+					while let Token::Accessor = peek_past_breaks() {
+						while let Token::LineBreak = peek() {
+							new_ln();
+							next();
+						}
 						next();
 
 						while let Token::LineBreak = peek() {
@@ -170,12 +189,14 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 									next(),
 									&next,
 									&peek,
+									&peek_past_breaks,
 									&get_ln,
 									&new_ln,
 									true,
 								)?),
 							},
 							ln: get_ln(),
+							start_ln,
 						};
 					}
 
@@ -185,6 +206,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 						name: name.clone(),
 						parameters,
 						body_fn,
+						doc: None,
 					}
 				}
 			}
@@ -218,6 +240,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 						next(),
 						&next,
 						&peek,
+						&peek_past_breaks,
 						&get_ln,
 						&new_ln,
 						false,
@@ -261,6 +284,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 						next(),
 						&next,
 						&peek,
+						&peek_past_breaks,
 						&get_ln,
 						&new_ln,
 						false,
@@ -290,6 +314,31 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 			Token::String(v) => Node::String(v.clone()),
 			Token::Name(v) => Node::Name(v.clone()),
 			Token::None => Node::None,
+			Token::Comment(v) => {
+				for _ in v.matches('\n') {
+					new_ln();
+				}
+				Node::Comment(v.clone())
+			}
+			Token::BlankLine => Node::EmptyLine,
+			Token::DocComment(v) => {
+				for _ in v.matches('\n') {
+					new_ln();
+				}
+				let mut pos_node = parse_token(
+					next(),
+					next,
+					peek,
+					peek_past_breaks,
+					get_ln,
+					new_ln,
+					prevent_accessor,
+				)?;
+				if let Node::FnCall { doc, .. } = &mut pos_node.node {
+					*doc = Some(v.clone());
+				}
+				return Ok(pos_node);
+			}
 			Token::EOF => {
 				return Err(Error::new(
 					"Unexpected end of input. (How did this happen?)",
@@ -304,7 +353,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 			}
 		};
 
-		return Ok(PosNode { node, ln: get_ln() });
+		return Ok(PosNode { node, ln: get_ln(), start_ln });
 	}
 
 	while i.get() < tokens.len() - 1 {
@@ -312,6 +361,7 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 			next(),
 			&next,
 			&peek,
+			&peek_past_breaks,
 			&get_ln,
 			&new_ln,
 			false,
@@ -321,5 +371,6 @@ pub fn parse(tokens: Vec<Token>) -> Result<PosNode, Error> {
 	Ok(PosNode {
 		node: Node::Program { body },
 		ln: 0,
+		start_ln: 0,
 	})
 }
@@ -0,0 +1,186 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	time::{Duration, Instant},
+};
+
+use crate::{
+	data::Data,
+	error::Error,
+	hook::ExecutionHook,
+	parser::PosNode,
+	scope::{function::Function, ScopeRef},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FunctionStats {
+	pub calls: usize,
+	/// Time spent in the function, including the functions it called.
+	pub inclusive: Duration,
+	/// Time spent in the function itself.
+	pub exclusive: Duration,
+}
+
+#[derive(Debug)]
+struct Frame {
+	name: String,
+	start: Instant,
+	children: Duration,
+}
+
+/// Records call counts, times and line hits of a program. Register it as an
+/// [`ExecutionHook`] and call [`Profiler::finish`] after evaluation.
+#[derive(Debug)]
+pub struct Profiler {
+	main_file: PathBuf,
+	stack: Vec<Frame>,
+	functions: HashMap<String, FunctionStats>,
+	lines: HashMap<(PathBuf, usize), usize>,
+	folded: HashMap<String, Duration>,
+}
+
+impl Profiler {
+	/// `main_file` is the path of the program, used in place of the directory
+	/// its module is keyed by.
+	pub fn new(main_file: PathBuf) -> Self {
+		Self {
+			main_file,
+			stack: vec![Frame {
+				name: String::from("[main]"),
+				start: Instant::now(),
+				children: Duration::ZERO,
+			}],
+			functions: HashMap::from([(
+				String::from("[main]"),
+				FunctionStats {
+					calls: 1,
+					..Default::default()
+				},
+			)]),
+			lines: HashMap::new(),
+			folded: HashMap::new(),
+		}
+	}
+
+	fn pop(&mut self) {
+		let Some(frame) = self.stack.pop() else {
+			return;
+		};
+		let elapsed = frame.start.elapsed();
+		let exclusive = elapsed.saturating_sub(frame.children);
+
+		let mut path: Vec<&str> = self.stack.iter().map(|f| f.name.as_str()).collect();
+		path.push(&frame.name);
+		*self.folded.entry(path.join(";")).or_default() += exclusive;
+
+		// Recursive calls are already counted by the outermost call.
+		let recursive = self.stack.iter().any(|f| f.name == frame.name);
+		let stats = self.functions.entry(frame.name).or_default();
+		stats.exclusive += exclusive;
+		if !recursive {
+			stats.inclusive += elapsed;
+		}
+		if let Some(parent) = self.stack.last_mut() {
+			parent.children += elapsed;
+		}
+	}
+
+	/// Closes any calls still open, such as after an error, and the main
+	/// program.
+	pub fn finish(&mut self) {
+		while !self.stack.is_empty() {
+			self.pop();
+		}
+	}
+
+	pub fn functions(&self) -> &HashMap<String, FunctionStats> {
+		&self.functions
+	}
+
+	/// Number of times each statement line was reached, keyed by file and
+	/// line.
+	pub fn lines(&self) -> &HashMap<(PathBuf, usize), usize> {
+		&self.lines
+	}
+
+	/// Exclusive time of each call stack in microseconds, one stack per line
+	/// with frames separated by `;`. This is the input format of flamegraph
+	/// tools.
+	pub fn folded(&self) -> String {
+		let mut stacks: Vec<(&String, &Duration)> = self.folded.iter().collect();
+		stacks.sort();
+		stacks
+			.iter()
+			.map(|(stack, time)| format!("{} {}\n", stack, time.as_micros()))
+			.collect()
+	}
+
+	/// A table of functions sorted by exclusive time, followed by the most
+	/// reached lines.
+	pub fn summary(&self, max_lines: usize) -> String {
+		let mut functions: Vec<(&String, &FunctionStats)> = self.functions.iter().collect();
+		functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+		let mut lines: Vec<(&(PathBuf, usize), &usize)> = self.lines.iter().collect();
+		lines.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+		let lines: Vec<(String, usize)> = lines
+			.into_iter()
+			.take(max_lines)
+			.map(|((file, ln), hits)| (format!("{}:{}", file.display(), ln), *hits))
+			.collect();
+
+		let width = functions
+			.iter()
+			.map(|f| f.0.len())
+			.chain(lines.iter().map(|l| l.0.len()))
+			.max()
+			.unwrap_or(0)
+			.max(8);
+
+		let mut summary = format!(
+			"{:<width$}  {:>8}  {:>12}  {:>12}\n",
+			"function", "calls", "total (ms)", "self (ms)"
+		);
+		for (name, stats) in functions {
+			summary += &format!(
+				"{:<width$}  {:>8}  {:>12.3}  {:>12.3}\n",
+				name,
+				stats.calls,
+				stats.inclusive.as_secs_f64() * 1000.0,
+				stats.exclusive.as_secs_f64() * 1000.0,
+			);
+		}
+
+		summary += &format!("\n{:<width$}  {:>8}\n", "line", "hits");
+		for (line, hits) in lines {
+			summary += &format!("{:<width$}  {:>8}\n", line, hits);
+		}
+
+		summary
+	}
+}
+
+impl ExecutionHook for Profiler {
+	fn node_enter(&mut self, file: &Path, node: &PosNode, _scope: &ScopeRef, statement: bool) {
+		if statement {
+			let file = if self.main_file.parent() == Some(file) {
+				self.main_file.clone()
+			} else {
+				file.to_path_buf()
+			};
+			*self.lines.entry((file, node.start_ln)).or_default() += 1;
+		}
+	}
+
+	fn call(&mut self, name: &str, _function: &Function, _args: &[Data], _scope: &ScopeRef) {
+		self.functions.entry(String::from(name)).or_default().calls += 1;
+		self.stack.push(Frame {
+			name: String::from(name),
+			start: Instant::now(),
+			children: Duration::ZERO,
+		});
+	}
+
+	fn call_return(&mut self, _name: &str, _function: &Function, _result: &Result<Data, Error>) {
+		self.pop();
+	}
+}
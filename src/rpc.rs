@@ -0,0 +1,36 @@
+//! Message framing shared by the language server and the debug adapter: JSON
+//! bodies preceded by a `Content-Length` header.
+
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<Value>> {
+	let mut length = None;
+	loop {
+		let mut line = String::new();
+		if reader.read_line(&mut line)? == 0 {
+			return Ok(None);
+		}
+		let line = line.trim_end();
+		if line.is_empty() {
+			break;
+		}
+		if let Some((key, value)) = line.split_once(':') {
+			if key.eq_ignore_ascii_case("content-length") {
+				length = value.trim().parse::<usize>().ok();
+			}
+		}
+	}
+
+	let mut body = vec![0; length.unwrap_or(0)];
+	reader.read_exact(&mut body)?;
+	Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+pub fn send(message: Value) -> io::Result<()> {
+	let body = message.to_string();
+	let mut stdout = io::stdout().lock();
+	write!(stdout, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+	stdout.flush()
+}
@@ -1,6 +1,9 @@
-use std::{any::Any, cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+use std::{any::Any, collections::HashMap, fmt::Debug};
 
-use crate::data::Data;
+use crate::{
+	data::Data,
+	util::{MaybeSync, MutRc},
+};
 use function::{CallScope, Function};
 
 use self::block_scope::IfState;
@@ -8,9 +11,9 @@ use self::block_scope::IfState;
 pub mod block_scope;
 pub mod function;
 
-pub type ScopeRef = Rc<RefCell<dyn Scope>>;
+pub type ScopeRef = MutRc<dyn Scope>;
 
-pub trait Scope: Debug {
+pub trait Scope: Debug + MaybeSync {
 	fn has_function(&self, name: &str) -> bool;
 	fn get_function(&self, name: &str) -> Option<Function>;
 	fn set_function(&mut self, name: &str, function: Function);
@@ -20,7 +23,7 @@ pub trait Scope: Debug {
 		None
 	}
 
-	fn get_call_scope(&self) -> Option<Rc<RefCell<CallScope>>> {
+	fn get_call_scope(&self) -> Option<MutRc<CallScope>> {
 		self.parent().map_or(None, |p| p.borrow().get_call_scope())
 	}
 	fn get_file_module(&self) -> Option<ScopeRef> {
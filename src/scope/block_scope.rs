@@ -1,8 +1,9 @@
-use std::{
-	any::Any, borrow::Borrow, cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc,
-};
+use std::{any::Any, borrow::Borrow, collections::HashMap, fmt::Debug};
 
-use crate::data::Data;
+use crate::{
+	data::Data,
+	util::{Lock, Shared},
+};
 
 use super::{function::Function, Scope, ScopeRef};
 
@@ -58,7 +59,7 @@ impl Scope for BlockScope {
 		if self.local_functions.contains_key(name) {
 			true
 		} else if let Some(parent) = &self.parent {
-			let borrow: &RefCell<dyn Scope> = parent.borrow();
+			let borrow: &Lock<dyn Scope> = parent.borrow();
 			borrow.borrow().has_function(name)
 		} else {
 			false
@@ -70,7 +71,7 @@ impl Scope for BlockScope {
 		if function.is_some() {
 			function.map(|x| x.clone())
 		} else if let Some(parent) = &self.parent {
-			let borrow: &RefCell<dyn Scope> = parent.borrow();
+			let borrow: &Lock<dyn Scope> = parent.borrow();
 			borrow.borrow().get_function(name).map(|x| x.clone())
 		} else {
 			None
@@ -90,7 +91,7 @@ impl Scope for BlockScope {
 	}
 
 	fn parent(&self) -> Option<ScopeRef> {
-		self.parent.as_ref().map(|x| Rc::clone(x))
+		self.parent.as_ref().map(|x| Shared::clone(x))
 	}
 
 	fn set_return_value(&mut self, value: Data) {
@@ -1,62 +1,71 @@
 use core::fmt::Debug;
-use std::{any::Any, cell::RefCell, collections::HashMap, rc::Rc};
+use std::{any::Any, collections::HashMap};
 
 use crate::{
-	data::Data,
+	data::{Data, DataType},
 	error::{Error, ErrorSource},
 	evaluator,
-	modules::loader::ModuleWrapper,
+	modules::{bean_std::collections::List, loader::ModuleWrapper},
 	parser::PosNode,
-	util::make_ref,
+	util::{make_ref, Lock, Shared},
 };
 
-use super::{Scope, ScopeRef};
+use super::{block_scope::BlockScope, Scope, ScopeRef};
 
 #[derive(Debug, Clone)]
 pub struct CallScope {
 	parent: ScopeRef,
-	arguments: Rc<Vec<Data>>,
-	body_fn: Rc<Option<Function>>,
+	local_functions: HashMap<String, Function>,
+	arguments: Shared<Vec<Data>>,
+	body_fn: Shared<Option<Function>>,
 	from_scope: ScopeRef,
 }
 
 impl CallScope {
-	pub fn args(&self) -> Rc<Vec<Data>> {
-		Rc::clone(&self.arguments)
+	pub fn args(&self) -> Shared<Vec<Data>> {
+		Shared::clone(&self.arguments)
 	}
 
-	pub fn body_fn(&self) -> Rc<Option<Function>> {
-		Rc::clone(&self.body_fn)
+	pub fn body_fn(&self) -> Shared<Option<Function>> {
+		Shared::clone(&self.body_fn)
 	}
 
 	pub fn from_scope(&self) -> ScopeRef {
-		Rc::clone(&self.from_scope)
+		Shared::clone(&self.from_scope)
+	}
+
+	pub fn local_functions(&self) -> &HashMap<String, Function> {
+		&self.local_functions
 	}
 }
 
 impl Scope for CallScope {
 	fn has_function(&self, name: &str) -> bool {
-		RefCell::borrow(&self.parent).has_function(name)
+		self.local_functions.contains_key(name)
+			|| Lock::borrow(&self.parent).has_function(name)
 	}
 
 	fn get_function(&self, name: &str) -> Option<Function> {
-		RefCell::borrow(&self.parent).get_function(name)
+		self.local_functions
+			.get(name)
+			.cloned()
+			.or_else(|| Lock::borrow(&self.parent).get_function(name))
 	}
 
 	fn set_function(&mut self, name: &str, function: Function) {
-		self.parent.borrow_mut().set_function(name, function)
+		self.local_functions.insert(String::from(name), function);
 	}
 
 	fn delete_function(&mut self, name: &str) {
-		self.parent.borrow_mut().delete_function(name)
+		self.local_functions.remove(name);
 	}
 
 	fn parent(&self) -> Option<ScopeRef> {
-		Some(Rc::clone(&self.parent) as ScopeRef)
+		Some(Shared::clone(&self.parent) as ScopeRef)
 	}
 
-	fn get_call_scope(&self) -> Option<Rc<RefCell<CallScope>>> {
-		Some(Rc::new(RefCell::new(self.clone())))
+	fn get_call_scope(&self) -> Option<Shared<Lock<CallScope>>> {
+		Some(Shared::new(Lock::new(self.clone())))
 	}
 
 	fn set_return_value(&mut self, _value: Data) {}
@@ -74,15 +83,275 @@ impl Scope for CallScope {
 	fn set_if_state(&mut self, _state: super::block_scope::IfState) {}
 }
 
+/// Splits a name like `<count: number>` into the name and its declared
+/// type. Names without an annotation are typed as `any`.
+pub fn parse_annotation(name: &str) -> Result<(String, DataType), Error> {
+	match name.split_once(':') {
+		Some((name, type_str)) => Ok((
+			String::from(name.trim()),
+			DataType::from_string(type_str)?,
+		)),
+		None => Ok((String::from(name.trim()), DataType::Any)),
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Parameter {
+	pub name: String,
+	pub param_type: DataType,
+	pub default: Option<Data>,
+	pub rest: bool,
+}
+
+impl Parameter {
+	pub fn new(annotated_name: &str, default: Option<Data>, rest: bool) -> Result<Self, Error> {
+		let (name, param_type) = parse_annotation(annotated_name)?;
+		if let Some(value) = &default {
+			if !param_type.matches(value) {
+				return Err(Error::new(
+					&format!(
+						"Default value for parameter {} must be {}, but got {}.",
+						name,
+						param_type.to_string(),
+						value.get_type().to_string()
+					),
+					ErrorSource::Internal,
+				));
+			}
+		}
+
+		Ok(Self {
+			name,
+			param_type,
+			default,
+			rest,
+		})
+	}
+
+	pub fn from_data(data: &Data) -> Result<Parameter, Error> {
+		match data {
+			Data::Name { name, .. } => Parameter::new(name, None, false),
+			Data::Scope(scope) => Lock::borrow(scope)
+				.as_any()
+				.downcast_ref::<Parameter>()
+				.cloned()
+				.ok_or_else(|| {
+					Error::new(
+						"Expected name or parameter, but instead got scope.",
+						ErrorSource::Internal,
+					)
+				}),
+			_ => Err(Error::new(
+				&format!(
+					"Expected name or parameter, but instead got {}.",
+					data.get_type().to_string()
+				),
+				ErrorSource::Internal,
+			)),
+		}
+	}
+}
+
+impl Scope for Parameter {
+	fn has_function(&self, _name: &str) -> bool {
+		false
+	}
+
+	fn get_function(&self, _name: &str) -> Option<Function> {
+		None
+	}
+
+	fn set_function(&mut self, _name: &str, _function: Function) {}
+	fn delete_function(&mut self, _name: &str) {}
+	fn set_return_value(&mut self, _value: Data) {}
+	fn get_function_list(&self) -> HashMap<String, Function> {
+		HashMap::new()
+	}
+
+	fn as_any(&self) -> &dyn Any {
+		self
+	}
+	fn as_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+
+	fn to_string(&self) -> String {
+		if self.rest {
+			format!("[parameter ...{}]", self.name)
+		} else {
+			format!("[parameter {}]", self.name)
+		}
+	}
+
+	fn set_if_state(&mut self, _state: super::block_scope::IfState) {}
+}
+
+#[derive(Debug)]
+pub struct Signature {
+	pub name: String,
+	pub parameters: Vec<Parameter>,
+}
+
+impl Signature {
+	pub fn new(name: &str, parameters: &[Data]) -> Result<Self, Error> {
+		let mut list: Vec<Parameter> = Vec::new();
+		for data in parameters {
+			let parameter = Parameter::from_data(data)?;
+			if list.last().is_some_and(|p| p.rest) {
+				return Err(Error::new(
+					&format!(
+						"Rest parameter must be the last parameter of function {}.",
+						name
+					),
+					ErrorSource::Internal,
+				));
+			} else if parameter.default.is_none()
+				&& !parameter.rest
+				&& list.iter().any(|p| p.default.is_some())
+			{
+				return Err(Error::new(
+					&format!(
+						"Required parameter {} of function {} cannot follow an optional parameter.",
+						parameter.name, name
+					),
+					ErrorSource::Internal,
+				));
+			}
+			list.push(parameter);
+		}
+
+		Ok(Self {
+			name: String::from(name),
+			parameters: list,
+		})
+	}
+
+	fn check_argument(&self, parameter: &Parameter, value: &Data) -> Result<(), Error> {
+		if parameter.param_type.matches(value) {
+			Ok(())
+		} else {
+			Err(Error::new(
+				&format!(
+					"Function {} expected parameter {} to be {}, but got {}.",
+					self.name,
+					parameter.name,
+					parameter.param_type.to_string(),
+					value.get_type().to_string()
+				),
+				ErrorSource::Internal,
+			))
+		}
+	}
+
+	fn bind(&self, args: &[Data]) -> Result<HashMap<String, Function>, Error> {
+		let required = self
+			.parameters
+			.iter()
+			.filter(|p| p.default.is_none() && !p.rest)
+			.count();
+		let has_rest = self.parameters.iter().any(|p| p.rest);
+		let positional = self.parameters.iter().filter(|p| !p.rest).count();
+
+		if args.len() < required {
+			return Err(Error::new(
+				&format!(
+					"Function {} expected {} {} argument{}, but got {}.",
+					self.name,
+					if required == positional && !has_rest {
+						"exactly"
+					} else {
+						"at least"
+					},
+					required,
+					if required == 1 { "" } else { "s" },
+					args.len()
+				),
+				ErrorSource::Internal,
+			));
+		} else if args.len() > positional && !has_rest {
+			return Err(Error::new(
+				&format!(
+					"Function {} expected {} {} argument{}, but got {}.",
+					self.name,
+					if required == positional {
+						"exactly"
+					} else {
+						"at most"
+					},
+					positional,
+					if positional == 1 { "" } else { "s" },
+					args.len()
+				),
+				ErrorSource::Internal,
+			));
+		}
+
+		let mut bindings = HashMap::new();
+		for (i, parameter) in self.parameters.iter().enumerate() {
+			let value = if parameter.rest {
+				let items = args.get(i..).map(|a| a.to_vec()).unwrap_or_default();
+				for item in &items {
+					self.check_argument(parameter, item)?;
+				}
+				Data::Scope(make_ref(List::new(items, None)))
+			} else if let Some(arg) = args.get(i) {
+				self.check_argument(parameter, arg)?;
+				arg.clone()
+			} else {
+				parameter.default.clone().unwrap_or_default()
+			};
+			bindings.insert(parameter.name.clone(), Function::Constant { value });
+		}
+
+		Ok(bindings)
+	}
+}
+
+/// Type a function declared to return, as in `fn(<name: number>)`. It is
+/// checked separately from the parameters, since a function may declare
+/// one without the other.
+#[derive(Debug)]
+pub struct ReturnType {
+	pub name: String,
+	pub return_type: DataType,
+}
+
+impl ReturnType {
+	fn check(&self, value: Data) -> Result<Data, Error> {
+		if self.return_type.matches(&value) {
+			Ok(value)
+		} else {
+			Err(Error::new(
+				&format!(
+					"Function {} expected to return {}, but returned {}.",
+					self.name,
+					self.return_type.to_string(),
+					value.get_type().to_string()
+				),
+				ErrorSource::Internal,
+			))
+		}
+	}
+}
+
+/// Callback of a builtin function, called with its arguments, body and the
+/// scope it was called in.
+#[cfg(not(feature = "sync"))]
+pub type BuiltinFn = dyn Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error>;
+#[cfg(feature = "sync")]
+pub type BuiltinFn =
+	dyn Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error> + Send + Sync;
+
 #[derive(Clone)]
 pub enum Function {
 	Custom {
-		body: Rc<PosNode>,
+		body: Shared<PosNode>,
 		scope_ref: ScopeRef,
+		signature: Option<Shared<Signature>>,
+		return_type: Option<Shared<ReturnType>>,
 	},
 	BuiltIn {
-		callback:
-			Rc<dyn Fn(Vec<Data>, Option<Function>, ScopeRef) -> Result<Data, Error>>,
+		callback: Shared<BuiltinFn>,
 	},
 	Variable {
 		value: Data,
@@ -105,21 +374,35 @@ impl Function {
 		from_scope: Option<ScopeRef>,
 	) -> Result<Data, Error> {
 		match self {
-			Function::Custom { body, scope_ref } => evaluator::evaluate_verbose(
+			Function::Custom {
 				body,
-				if abstract_call_scope {
+				scope_ref,
+				signature,
+				return_type,
+			} => {
+				let call_scope: ScopeRef = if abstract_call_scope {
+					let local_functions = match signature {
+						Some(signature) => signature.bind(&args)?,
+						None => HashMap::new(),
+					};
 					make_ref(CallScope {
-						parent: Rc::clone(&scope_ref),
-						arguments: Rc::new(args),
-						body_fn: Rc::new(body_fn),
-						from_scope: Rc::clone(from_scope.as_ref().unwrap_or(&scope)),
+						parent: Shared::clone(scope_ref),
+						local_functions,
+						arguments: Shared::new(args),
+						body_fn: Shared::new(body_fn),
+						from_scope: Shared::clone(from_scope.as_ref().unwrap_or(&scope)),
 					})
 				} else {
 					scope
-				},
-				return_scope,
-				None,
-			),
+				};
+				let result =
+					evaluator::evaluate_verbose(body, call_scope, return_scope, None)?;
+
+				match return_type {
+					Some(return_type) if abstract_call_scope => return_type.check(result),
+					_ => Ok(result),
+				}
+			}
 			Function::BuiltIn { callback } => {
 				if from_scope.is_some() && scope.borrow().as_any().is::<ModuleWrapper>() {
 					callback(args, body_fn, from_scope.unwrap())
@@ -134,12 +417,12 @@ impl Function {
 			} => {
 				if let Some(v) = body_fn {
 					let pass = value.clone();
-					let value = v.call(Vec::new(), None, Rc::clone(&scope))?;
+					let value = v.call(Vec::new(), None, Shared::clone(&scope))?;
 					scope_ref.borrow_mut().set_function(
 						name,
 						Function::Variable {
 							value,
-							scope_ref: Rc::clone(scope_ref),
+							scope_ref: Shared::clone(scope_ref),
 							name: String::from(name),
 						},
 					);
@@ -194,14 +477,135 @@ impl Function {
 	) -> Result<Data, Error> {
 		self.call_verbose(args, body_fn, scope, false, false, None)
 	}
+
+	/// Returns a copy of this function that binds its arguments by name
+	/// according to `signature` when called. Only custom functions can
+	/// declare parameters.
+	pub fn with_signature(self, signature: Signature) -> Result<Function, Error> {
+		match self {
+			Function::Custom {
+				body,
+				scope_ref,
+				return_type,
+				..
+			} => Ok(Function::Custom {
+				body,
+				scope_ref,
+				signature: Some(Shared::new(signature)),
+				return_type,
+			}),
+			_ => Err(Error::new(
+				&format!(
+					"Cannot declare parameters for function {}, because it is not a custom function.",
+					signature.name
+				),
+				ErrorSource::Internal,
+			)),
+		}
+	}
+
+	/// Returns a copy of this function that checks its return value
+	/// against `return_type` when called.
+	pub fn with_return_type(self, return_type: ReturnType) -> Result<Function, Error> {
+		match self {
+			Function::Custom {
+				body,
+				scope_ref,
+				signature,
+				..
+			} => Ok(Function::Custom {
+				body,
+				scope_ref,
+				signature,
+				return_type: Some(Shared::new(return_type)),
+			}),
+			_ => Err(Error::new(
+				&format!(
+					"Cannot declare a return type for function {}, because it is not a custom function.",
+					return_type.name
+				),
+				ErrorSource::Internal,
+			)),
+		}
+	}
+
+	/// Returns a copy of this function that captures a fresh child scope
+	/// of its defining scope, holding each binding as a constant. Used to
+	/// give loop variables a new binding on every iteration.
+	pub fn with_bindings(&self, bindings: Vec<(&str, Data)>) -> Function {
+		match self {
+			Function::Custom {
+				body,
+				scope_ref,
+				signature,
+				return_type,
+			} => {
+				let mut scope = BlockScope::new(Some(Shared::clone(scope_ref)));
+				for (name, value) in bindings {
+					scope.set_function(name, Function::Constant { value });
+				}
+
+				Function::Custom {
+					body: Shared::clone(body),
+					scope_ref: make_ref(scope),
+					signature: signature.clone(),
+					return_type: return_type.clone(),
+				}
+			}
+			_ => self.clone(),
+		}
+	}
+
+	pub fn ptr_eq(&self, other: &Function) -> bool {
+		match (self, other) {
+			(
+				Function::Custom {
+					body, scope_ref, ..
+				},
+				Function::Custom {
+					body: r_body,
+					scope_ref: r_scope_ref,
+					..
+				},
+			) => Shared::ptr_eq(body, r_body) && Shared::ptr_eq(scope_ref, r_scope_ref),
+			(
+				Function::BuiltIn { callback },
+				Function::BuiltIn {
+					callback: r_callback,
+				},
+			) => Shared::ptr_eq(callback, r_callback),
+			(
+				Function::Variable {
+					scope_ref, name, ..
+				},
+				Function::Variable {
+					scope_ref: r_scope_ref,
+					name: r_name,
+					..
+				},
+			) => Shared::ptr_eq(scope_ref, r_scope_ref) && name == r_name,
+			(Function::Constant { value }, Function::Constant { value: r_value }) => {
+				value == r_value
+			}
+			_ => false,
+		}
+	}
 }
 
 impl Debug for Function {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
-			Self::Custom { body, scope_ref: _ } => {
-				f.debug_struct("Custom").field("body", body).finish()
-			}
+			Self::Custom {
+				body,
+				signature,
+				return_type,
+				..
+			} => f
+				.debug_struct("Custom")
+				.field("body", body)
+				.field("signature", signature)
+				.field("return_type", return_type)
+				.finish(),
 			Self::BuiltIn { .. } => f.debug_struct("BuiltIn").finish(),
 			Self::Variable {
 				value,
@@ -0,0 +1,836 @@
+use std::{
+	cell::RefCell,
+	fmt::{self, Display, Formatter},
+};
+
+use serde::{
+	de::{
+		self,
+		value::{MapDeserializer, SeqDeserializer},
+		DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+		VariantAccess, Visitor,
+	},
+	forward_to_deserialize_any,
+	ser::{
+		self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant,
+		SerializeTuple, SerializeTupleStruct, SerializeTupleVariant,
+	},
+	Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{
+	data::Data,
+	error::{Error, ErrorSource},
+	modules::bean_std::collections::{List, Map},
+	scope::{function::Function, ScopeRef},
+	util::{make_ref, Lock, Shared},
+};
+
+/// Error raised while converting between data and serde types.
+#[derive(Debug)]
+pub struct SerdeError(String);
+
+impl Display for SerdeError {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+	fn custom<T: Display>(msg: T) -> Self {
+		Self(msg.to_string())
+	}
+}
+
+impl de::Error for SerdeError {
+	fn custom<T: Display>(msg: T) -> Self {
+		Self(msg.to_string())
+	}
+}
+
+impl From<SerdeError> for Error {
+	fn from(error: SerdeError) -> Self {
+		Error::new(&error.0, ErrorSource::Internal)
+	}
+}
+
+/// Converts a serializable value into data. Sequences become lists, maps and
+/// structs become maps, and enum variants with values become maps with a
+/// single key.
+pub fn to_data<T: Serialize + ?Sized>(value: &T) -> Result<Data, Error> {
+	value.serialize(DataSerializer).map_err(Error::from)
+}
+
+/// Converts data into a deserializable value. Scopes other than lists and
+/// maps are read as maps of their variables.
+pub fn from_data<T: DeserializeOwned>(data: Data) -> Result<T, Error> {
+	T::deserialize(data).map_err(Error::from)
+}
+
+/// Largest number below which every whole number is exact as an `f64`.
+const MAX_EXACT: f64 = 9007199254740992.0;
+
+fn integer(v: f64) -> Option<i64> {
+	(v.fract() == 0.0 && v.abs() <= MAX_EXACT).then_some(v as i64)
+}
+
+fn list(items: Vec<Data>) -> Data {
+	Data::Scope(make_ref(List::new(items, None)))
+}
+
+fn map(pairs: Vec<(Data, Data)>) -> Data {
+	let kv_pairs = pairs.into_iter().flat_map(|(k, v)| [k, v]).collect();
+	Data::Scope(make_ref(Map::new(kv_pairs, None)))
+}
+
+enum Contents {
+	List(Vec<Data>),
+	Map(Vec<(Data, Data)>),
+}
+
+/// Items of a list, entries of a map, or the variables of any other scope
+/// by name.
+fn contents(scope: &ScopeRef) -> Contents {
+	let scope = Lock::borrow(scope);
+	if let Some(list) = scope.as_any().downcast_ref::<List>() {
+		return Contents::List(list.items.iter().cloned().collect());
+	}
+	if let Some(map) = scope.as_any().downcast_ref::<Map>() {
+		return Contents::Map(map.hash.iter().map(|(k, v)| (k.clone(), v.clone())).collect());
+	}
+
+	let mut variables: Vec<(String, Data)> = scope
+		.get_function_list()
+		.into_iter()
+		.filter_map(|(name, function)| match function {
+			Function::Variable { value, .. } | Function::Constant { value } => Some((name, value)),
+			_ => None,
+		})
+		.collect();
+	variables.sort_by(|(a, _), (b, _)| a.cmp(b));
+	Contents::Map(
+		variables
+			.into_iter()
+			.map(|(name, value)| (Data::String(name), value))
+			.collect(),
+	)
+}
+
+thread_local! {
+	/// Scopes whose contents are being converted on this thread, innermost
+	/// last.
+	static CONVERTING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a scope as being converted until dropped.
+struct Converting;
+
+impl Drop for Converting {
+	fn drop(&mut self) {
+		CONVERTING.with(|scopes| scopes.borrow_mut().pop());
+	}
+}
+
+/// Calls `f` with the contents of a scope. A scope that contains itself
+/// would be converted forever, so this fails with `error` if the scope is
+/// already being converted further up.
+fn with_contents<T, E>(
+	scope: &ScopeRef,
+	error: impl FnOnce(&'static str) -> E,
+	f: impl FnOnce(Contents) -> Result<T, E>,
+) -> Result<T, E> {
+	let ptr = Shared::as_ptr(scope) as *const () as usize;
+	if CONVERTING.with(|scopes| scopes.borrow().contains(&ptr)) {
+		return Err(error("Cannot convert a scope that contains itself."));
+	}
+	CONVERTING.with(|scopes| scopes.borrow_mut().push(ptr));
+	let _converting = Converting;
+	f(contents(scope))
+}
+
+impl Serialize for Data {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self {
+			Data::Boolean(v) => serializer.serialize_bool(*v),
+			Data::Number(v) => match integer(*v) {
+				Some(i) => serializer.serialize_i64(i),
+				None => serializer.serialize_f64(*v),
+			},
+			Data::String(v) => serializer.serialize_str(v),
+			Data::Name { name, .. } => serializer.serialize_str(name),
+			Data::Scope(scope) => with_contents(scope, ser::Error::custom, |contents| match contents {
+				Contents::List(items) => serializer.collect_seq(items),
+				Contents::Map(pairs) => serializer.collect_map(pairs),
+			}),
+			Data::None => serializer.serialize_unit(),
+			Data::Function(_) | Data::UserData(_) => Err(ser::Error::custom(format!(
+				"Cannot serialize {}.",
+				self.get_type().to_string()
+			))),
+		}
+	}
+}
+
+struct DataSerializer;
+
+struct SeqSerializer {
+	variant: Option<&'static str>,
+	items: Vec<Data>,
+}
+
+struct MapSerializer {
+	variant: Option<&'static str>,
+	pairs: Vec<(Data, Data)>,
+	key: Option<Data>,
+}
+
+fn with_variant(variant: Option<&'static str>, value: Data) -> Data {
+	match variant {
+		Some(variant) => map(vec![(Data::String(String::from(variant)), value)]),
+		None => value,
+	}
+}
+
+impl Serializer for DataSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+	type SerializeSeq = SeqSerializer;
+	type SerializeTuple = SeqSerializer;
+	type SerializeTupleStruct = SeqSerializer;
+	type SerializeTupleVariant = SeqSerializer;
+	type SerializeMap = MapSerializer;
+	type SerializeStruct = MapSerializer;
+	type SerializeStructVariant = MapSerializer;
+
+	fn serialize_bool(self, v: bool) -> Result<Data, SerdeError> {
+		Ok(Data::Boolean(v))
+	}
+
+	fn serialize_i8(self, v: i8) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_i16(self, v: i16) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_i32(self, v: i32) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_i64(self, v: i64) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_u8(self, v: u8) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_u16(self, v: u16) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_u32(self, v: u32) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_u64(self, v: u64) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_f32(self, v: f32) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn serialize_f64(self, v: f64) -> Result<Data, SerdeError> {
+		Ok(Data::Number(v))
+	}
+
+	fn serialize_char(self, v: char) -> Result<Data, SerdeError> {
+		Ok(Data::String(v.to_string()))
+	}
+
+	fn serialize_str(self, v: &str) -> Result<Data, SerdeError> {
+		Ok(Data::String(String::from(v)))
+	}
+
+	fn serialize_bytes(self, v: &[u8]) -> Result<Data, SerdeError> {
+		Ok(list(v.iter().map(|b| Data::Number(*b as f64)).collect()))
+	}
+
+	fn serialize_none(self) -> Result<Data, SerdeError> {
+		Ok(Data::None)
+	}
+
+	fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Data, SerdeError> {
+		value.serialize(self)
+	}
+
+	fn serialize_unit(self) -> Result<Data, SerdeError> {
+		Ok(Data::None)
+	}
+
+	fn serialize_unit_struct(self, _name: &'static str) -> Result<Data, SerdeError> {
+		Ok(Data::None)
+	}
+
+	fn serialize_unit_variant(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+	) -> Result<Data, SerdeError> {
+		Ok(Data::String(String::from(variant)))
+	}
+
+	fn serialize_newtype_struct<T: Serialize + ?Sized>(
+		self,
+		_name: &'static str,
+		value: &T,
+	) -> Result<Data, SerdeError> {
+		value.serialize(self)
+	}
+
+	fn serialize_newtype_variant<T: Serialize + ?Sized>(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		value: &T,
+	) -> Result<Data, SerdeError> {
+		Ok(with_variant(Some(variant), value.serialize(self)?))
+	}
+
+	fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, SerdeError> {
+		Ok(SeqSerializer {
+			variant: None,
+			items: Vec::with_capacity(len.unwrap_or_default()),
+		})
+	}
+
+	fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, SerdeError> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, SerdeError> {
+		self.serialize_seq(Some(len))
+	}
+
+	fn serialize_tuple_variant(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		len: usize,
+	) -> Result<SeqSerializer, SerdeError> {
+		Ok(SeqSerializer {
+			variant: Some(variant),
+			items: Vec::with_capacity(len),
+		})
+	}
+
+	fn serialize_map(self, len: Option<usize>) -> Result<MapSerializer, SerdeError> {
+		Ok(MapSerializer {
+			variant: None,
+			pairs: Vec::with_capacity(len.unwrap_or_default()),
+			key: None,
+		})
+	}
+
+	fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, SerdeError> {
+		self.serialize_map(Some(len))
+	}
+
+	fn serialize_struct_variant(
+		self,
+		_name: &'static str,
+		_index: u32,
+		variant: &'static str,
+		len: usize,
+	) -> Result<MapSerializer, SerdeError> {
+		Ok(MapSerializer {
+			variant: Some(variant),
+			pairs: Vec::with_capacity(len),
+			key: None,
+		})
+	}
+}
+
+impl SerializeSeq for SeqSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+		self.items.push(value.serialize(DataSerializer)?);
+		Ok(())
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		Ok(with_variant(self.variant, list(self.items)))
+	}
+}
+
+impl SerializeTuple for SeqSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+		SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		SerializeSeq::end(self)
+	}
+}
+
+impl SerializeTupleStruct for SeqSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+		SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		SerializeSeq::end(self)
+	}
+}
+
+impl SerializeTupleVariant for SeqSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+		SerializeSeq::serialize_element(self, value)
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		SerializeSeq::end(self)
+	}
+}
+
+impl SerializeMap for MapSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+		self.key = Some(key.serialize(DataSerializer)?);
+		Ok(())
+	}
+
+	fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+		let key = self
+			.key
+			.take()
+			.ok_or_else(|| SerdeError(String::from("Map value serialized before its key.")))?;
+		self.pairs.push((key, value.serialize(DataSerializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		Ok(with_variant(self.variant, map(self.pairs)))
+	}
+}
+
+impl SerializeStruct for MapSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(
+		&mut self,
+		key: &'static str,
+		value: &T,
+	) -> Result<(), SerdeError> {
+		self.pairs
+			.push((Data::String(String::from(key)), value.serialize(DataSerializer)?));
+		Ok(())
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		SerializeMap::end(self)
+	}
+}
+
+impl SerializeStructVariant for MapSerializer {
+	type Ok = Data;
+	type Error = SerdeError;
+
+	fn serialize_field<T: Serialize + ?Sized>(
+		&mut self,
+		key: &'static str,
+		value: &T,
+	) -> Result<(), SerdeError> {
+		SerializeStruct::serialize_field(self, key, value)
+	}
+
+	fn end(self) -> Result<Data, SerdeError> {
+		SerializeMap::end(self)
+	}
+}
+
+impl<'de> Deserializer<'de> for Data {
+	type Error = SerdeError;
+
+	fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+		match self {
+			Data::Boolean(v) => visitor.visit_bool(v),
+			Data::Number(v) => match integer(v) {
+				Some(i) => visitor.visit_i64(i),
+				None => visitor.visit_f64(v),
+			},
+			Data::String(v) => visitor.visit_string(v),
+			Data::Name { name, .. } => visitor.visit_string(name),
+			Data::Scope(scope) => with_contents(&scope, de::Error::custom, |contents| match contents {
+				Contents::List(items) => {
+					let mut seq = SeqDeserializer::new(items.into_iter());
+					let value = visitor.visit_seq(&mut seq)?;
+					seq.end()?;
+					Ok(value)
+				}
+				Contents::Map(pairs) => {
+					let mut map = MapDeserializer::new(pairs.into_iter());
+					let value = visitor.visit_map(&mut map)?;
+					map.end()?;
+					Ok(value)
+				}
+			}),
+			Data::None => visitor.visit_unit(),
+			Data::Function(_) | Data::UserData(_) => Err(de::Error::custom(format!(
+				"Cannot deserialize {}.",
+				self.get_type().to_string()
+			))),
+		}
+	}
+
+	fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+		match self {
+			Data::None => visitor.visit_none(),
+			data => visitor.visit_some(data),
+		}
+	}
+
+	fn deserialize_newtype_struct<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		visitor: V,
+	) -> Result<V::Value, SerdeError> {
+		visitor.visit_newtype_struct(self)
+	}
+
+	fn deserialize_enum<V: Visitor<'de>>(
+		self,
+		_name: &'static str,
+		_variants: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, SerdeError> {
+		if let Data::String(variant) = self {
+			return visitor.visit_enum(variant.into_deserializer());
+		}
+		let error = format!(
+			"Expected string or map with one entry for enum, but got {} instead.",
+			self.get_type().to_string()
+		);
+		let Data::Scope(scope) = &self else {
+			return Err(de::Error::custom(error));
+		};
+		with_contents(scope, de::Error::custom, |contents| match contents {
+			Contents::Map(mut pairs) if pairs.len() == 1 => {
+				let (variant, value) = pairs.remove(0);
+				visitor.visit_enum(EnumDeserializer { variant, value })
+			}
+			_ => Err(de::Error::custom(error)),
+		})
+	}
+
+	forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+		identifier ignored_any
+	}
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Data {
+	type Deserializer = Self;
+
+	fn into_deserializer(self) -> Self {
+		self
+	}
+}
+
+struct EnumDeserializer {
+	variant: Data,
+	value: Data,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+	type Error = SerdeError;
+	type Variant = Data;
+
+	fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Data), SerdeError> {
+		Ok((seed.deserialize(self.variant)?, self.value))
+	}
+}
+
+impl<'de> VariantAccess<'de> for Data {
+	type Error = SerdeError;
+
+	fn unit_variant(self) -> Result<(), SerdeError> {
+		<()>::deserialize(self)
+	}
+
+	fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, SerdeError> {
+		seed.deserialize(self)
+	}
+
+	fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, SerdeError> {
+		self.deserialize_seq(visitor)
+	}
+
+	fn struct_variant<V: Visitor<'de>>(
+		self,
+		_fields: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, SerdeError> {
+		self.deserialize_map(visitor)
+	}
+}
+
+impl<'de> Deserialize<'de> for Data {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		deserializer.deserialize_any(DataVisitor)
+	}
+}
+
+struct DataVisitor;
+
+impl<'de> Visitor<'de> for DataVisitor {
+	type Value = Data;
+
+	fn expecting(&self, f: &mut Formatter) -> fmt::Result {
+		f.write_str("a value representable in bean")
+	}
+
+	fn visit_bool<E: de::Error>(self, v: bool) -> Result<Data, E> {
+		Ok(Data::Boolean(v))
+	}
+
+	fn visit_i64<E: de::Error>(self, v: i64) -> Result<Data, E> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn visit_u64<E: de::Error>(self, v: u64) -> Result<Data, E> {
+		Ok(Data::Number(v as f64))
+	}
+
+	fn visit_f64<E: de::Error>(self, v: f64) -> Result<Data, E> {
+		Ok(Data::Number(v))
+	}
+
+	fn visit_str<E: de::Error>(self, v: &str) -> Result<Data, E> {
+		Ok(Data::String(String::from(v)))
+	}
+
+	fn visit_string<E: de::Error>(self, v: String) -> Result<Data, E> {
+		Ok(Data::String(v))
+	}
+
+	fn visit_none<E: de::Error>(self) -> Result<Data, E> {
+		Ok(Data::None)
+	}
+
+	fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Data, D::Error> {
+		Data::deserialize(deserializer)
+	}
+
+	fn visit_unit<E: de::Error>(self) -> Result<Data, E> {
+		Ok(Data::None)
+	}
+
+	fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Data, A::Error> {
+		let mut items = Vec::new();
+		while let Some(item) = seq.next_element()? {
+			items.push(item);
+		}
+		Ok(list(items))
+	}
+
+	fn visit_map<A: MapAccess<'de>>(self, mut map_access: A) -> Result<Data, A::Error> {
+		let mut pairs = Vec::new();
+		while let Some(pair) = map_access.next_entry()? {
+			pairs.push(pair);
+		}
+		Ok(map(pairs))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use serde::{Deserialize, Serialize};
+
+	use super::*;
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Point {
+		x: f64,
+		y: f64,
+		label: Option<String>,
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	enum Shape {
+		Empty,
+		Circle(f64),
+		Line(Point, Point),
+		Rect { width: f64, height: f64 },
+	}
+
+	fn point(x: f64, y: f64) -> Point {
+		Point { x, y, label: None }
+	}
+
+	fn round_trip(data: &Data) -> Data {
+		let json = serde_json::to_string(data).unwrap();
+		serde_json::from_str(&json).unwrap()
+	}
+
+	#[test]
+	fn scalars_round_trip() {
+		for data in [
+			Data::Number(1.5),
+			Data::Number(-3.0),
+			Data::Boolean(true),
+			Data::String(String::from("bean")),
+			Data::None,
+		] {
+			assert_eq!(round_trip(&data), data);
+		}
+		assert_eq!(serde_json::to_string(&Data::Number(2.0)).unwrap(), "2");
+	}
+
+	#[test]
+	fn lists_round_trip() {
+		let data = list(vec![
+			Data::Number(1.0),
+			Data::String(String::from("two")),
+			list(vec![Data::None]),
+		]);
+		assert_eq!(serde_json::to_string(&data).unwrap(), r#"[1,"two",[null]]"#);
+
+		let Data::Scope(scope) = round_trip(&data) else {
+			panic!("expected a list");
+		};
+		let Contents::List(items) = contents(&scope) else {
+			panic!("expected a list");
+		};
+		assert_eq!(items[..2], [Data::Number(1.0), Data::String(String::from("two"))]);
+		assert_eq!(from_data::<Vec<()>>(items[2].clone()).unwrap(), vec![()]);
+	}
+
+	#[test]
+	fn maps_round_trip() {
+		let data = map(vec![
+			(Data::String(String::from("a")), Data::Number(1.0)),
+			(Data::String(String::from("b")), Data::Number(2.0)),
+		]);
+		let Data::Scope(scope) = round_trip(&data) else {
+			panic!("expected a map");
+		};
+		assert!(matches!(contents(&scope), Contents::Map(_)));
+
+		let expected: HashMap<String, f64> =
+			[(String::from("a"), 1.0), (String::from("b"), 2.0)].into_iter().collect();
+		assert_eq!(from_data::<HashMap<String, f64>>(Data::Scope(scope)).unwrap(), expected);
+	}
+
+	#[test]
+	fn structs_round_trip() {
+		let data = to_data(&Point {
+			x: 1.0,
+			y: 2.0,
+			label: Some(String::from("origin")),
+		})
+		.unwrap();
+		let Data::Scope(scope) = &data else {
+			panic!("expected a map");
+		};
+		let Contents::Map(mut pairs) = contents(scope) else {
+			panic!("expected a map");
+		};
+		pairs.sort_by_key(|(k, _)| k.to_string());
+		assert_eq!(
+			pairs,
+			[
+				(Data::String(String::from("label")), Data::String(String::from("origin"))),
+				(Data::String(String::from("x")), Data::Number(1.0)),
+				(Data::String(String::from("y")), Data::Number(2.0)),
+			]
+		);
+
+		assert_eq!(from_data::<Point>(data).unwrap().label.as_deref(), Some("origin"));
+		let data = to_data(&point(3.0, 4.0)).unwrap();
+		assert_eq!(from_data::<Point>(data).unwrap(), point(3.0, 4.0));
+	}
+
+	#[test]
+	fn enums_round_trip() {
+		assert_eq!(to_data(&Shape::Empty).unwrap(), Data::String(String::from("Empty")));
+		for shape in [
+			Shape::Empty,
+			Shape::Circle(1.5),
+			Shape::Line(point(0.0, 0.0), point(1.0, 1.0)),
+			Shape::Rect {
+				width: 2.0,
+				height: 3.0,
+			},
+		] {
+			let data = to_data(&shape).unwrap();
+			assert_eq!(from_data::<Shape>(data).unwrap(), shape);
+		}
+
+		let error = from_data::<Shape>(Data::Number(1.0)).unwrap_err();
+		assert_eq!(
+			error.message(),
+			"Expected string or map with one entry for enum, but got number instead."
+		);
+	}
+
+	#[test]
+	fn tuples_and_options_round_trip() {
+		let tuple = (1u8, String::from("two"), Some(true), None::<f64>);
+		let data = to_data(&tuple).unwrap();
+		assert_eq!(serde_json::to_string(&data).unwrap(), r#"[1,"two",true,null]"#);
+		assert_eq!(from_data::<(u8, String, Option<bool>, Option<f64>)>(data).unwrap(), tuple);
+
+		assert_eq!(to_data(&None::<f64>).unwrap(), Data::None);
+		assert_eq!(from_data::<Option<f64>>(Data::None).unwrap(), None);
+		assert_eq!(from_data::<Option<f64>>(Data::Number(1.0)).unwrap(), Some(1.0));
+	}
+
+	#[test]
+	fn rejects_scopes_containing_themselves() {
+		let data = map(Vec::new());
+		let Data::Scope(scope) = &data else {
+			panic!("expected a map");
+		};
+		Lock::borrow_mut(scope)
+			.as_mut()
+			.downcast_mut::<Map>()
+			.unwrap()
+			.hash
+			.insert(Data::String(String::from("self")), data.clone());
+
+		let message = "Cannot convert a scope that contains itself.";
+		assert_eq!(serde_json::to_string(&data).unwrap_err().to_string(), message);
+		assert_eq!(to_data(&data).unwrap_err().message(), message);
+		assert_eq!(from_data::<Data>(data.clone()).unwrap_err().message(), message);
+
+		// A scope may appear more than once, as long as it is not inside itself.
+		let inner = list(vec![Data::Number(1.0)]);
+		let outer = list(vec![inner.clone(), inner]);
+		assert_eq!(serde_json::to_string(&outer).unwrap(), "[[1],[1]]");
+	}
+}
@@ -0,0 +1,298 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	process::ExitCode,
+};
+
+use bean_script::{
+	coverage::Coverage,
+	data::Data,
+	error::{Error, ErrorSource},
+	evaluator, lexer,
+	logger::Logger,
+	modules::{
+		bean_std::TestRun,
+		registry::{ModuleRegistry, RegistryFeatures},
+		CustomModule,
+	},
+	parser::{self, PosNode},
+	util::{diff_lines, make_ref, MutRc, Shared},
+};
+
+/// Finds files under `path` whose name is accepted by `filter`, or `path`
+/// itself if it is a file.
+fn discover(path: &Path, filter: &impl Fn(&str) -> bool, files: &mut Vec<PathBuf>) {
+	if !path.is_dir() {
+		files.push(path.to_path_buf());
+		return;
+	}
+	let Ok(entries) = fs::read_dir(path) else {
+		return;
+	};
+
+	let mut entries: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+	entries.sort();
+	for entry in entries {
+		if entry.is_dir() {
+			discover(&entry, filter, files);
+		} else if entry
+			.file_name()
+			.is_some_and(|name| filter(&name.to_string_lossy()))
+		{
+			files.push(entry);
+		}
+	}
+}
+
+struct Run {
+	result: Result<Data, Error>,
+	tests: TestRun,
+	output: Vec<String>,
+}
+
+fn parse_file(path: &Path) -> Result<PosNode, Error> {
+	fs::read_to_string(path)
+		.map_err(|e| Error::new(&format!("Failed to open file. {}", e), ErrorSource::Internal))
+		.and_then(|file| parser::parse(lexer::tokenize(file)))
+}
+
+/// Evaluates a file with a fresh registry, capturing its output. With a
+/// `tests` state, `test` calls are collected or selected by it.
+fn run_file(
+	path: &Path,
+	tree: &PosNode,
+	tests: Option<TestRun>,
+	coverage: Option<&MutRc<Coverage>>,
+	includes: &[PathBuf],
+) -> Run {
+	let mut dir = path.to_path_buf();
+	dir.pop();
+
+	let registry = make_ref(ModuleRegistry::new(RegistryFeatures::default()));
+	{
+		let mut registry = registry.borrow_mut();
+		crate::add_search_paths(&mut registry, includes);
+		registry.logger = Logger::Backlog {
+			backlog: Default::default(),
+		};
+		if let Some(tests) = tests {
+			registry
+				.metadata
+				.insert(String::from(TestRun::KEY), Box::new(tests));
+		}
+		if let Some(coverage) = coverage {
+			coverage
+				.borrow_mut()
+				.add_main(dir.clone(), path.to_path_buf(), tree);
+			registry.hooks.push(coverage.clone());
+		}
+	}
+
+	let program_scope = CustomModule::new(Shared::clone(&registry), dir);
+	let result = evaluator::evaluate(tree, make_ref(program_scope));
+
+	let mut registry = registry.borrow_mut();
+	let output = registry.logger.by_ref().collect();
+	let tests = registry
+		.metadata
+		.remove(TestRun::KEY)
+		.and_then(|run| run.downcast::<TestRun>().ok())
+		.map_or_else(TestRun::default, |run| *run);
+	Run {
+		result,
+		tests,
+		output,
+	}
+}
+
+/// Prints lines indented, coloring the lines of a diff.
+fn print_lines<'a>(lines: impl Iterator<Item = &'a str>) {
+	for line in lines {
+		if line.starts_with("- ") {
+			println!("\t\x1b[31m{}\x1b[0m", line);
+		} else if line.starts_with("+ ") {
+			println!("\t\x1b[32m{}\x1b[0m", line);
+		} else {
+			println!("\t{}", line);
+		}
+	}
+}
+
+fn report(path: &Path, error: &Error, output: &[String]) {
+	for line in output {
+		println!("\t{}", line);
+	}
+
+	let mut lines = error.message().lines();
+	println!("\t\x1b[31;1merror\x1b[0m: {}", lines.next().unwrap_or_default());
+	print_lines(lines);
+	if let Some(ln) = error.line() {
+		println!("\t\x1b[36m->\x1b[0m {}:{}", path.display(), ln);
+	}
+}
+
+/// Runs every test declared in `*_test.bean` files under `paths`, each with
+/// its own registry.
+pub fn run(paths: &[String], includes: &[PathBuf], coverage: bool) -> ExitCode {
+	let files = discover_all(paths, &|name| name.ends_with("_test.bean"));
+	if files.is_empty() {
+		println!("No test files found.");
+		return ExitCode::SUCCESS;
+	}
+
+	let coverage = coverage.then(|| make_ref(Coverage::new()));
+	let mut passed = 0;
+	let mut failed = 0;
+
+	for path in files {
+		let tree = match parse_file(&path) {
+			Ok(tree) => tree,
+			Err(error) => {
+				println!("\n{} \x1b[31mFAILED\x1b[0m", path.display());
+				report(&path, &error, &[]);
+				failed += 1;
+				continue;
+			}
+		};
+
+		let collected = run_file(
+			&path,
+			&tree,
+			Some(TestRun::default()),
+			coverage.as_ref(),
+			includes,
+		);
+		if let Err(error) = &collected.result {
+			println!("\n{} \x1b[31mFAILED\x1b[0m", path.display());
+			report(&path, error, &collected.output);
+			failed += 1;
+			continue;
+		}
+
+		let names = collected.tests.names;
+		println!("\n{} ({} tests)", path.display(), names.len());
+		for (i, name) in names.iter().enumerate() {
+			let tests = TestRun {
+				only: Some(i),
+				..Default::default()
+			};
+			let run = run_file(&path, &tree, Some(tests), coverage.as_ref(), includes);
+			let failure = match (run.tests.outcome, run.result) {
+				(Some(Err(error)), _) | (_, Err(error)) => Some(error),
+				(Some(Ok(())), Ok(_)) => None,
+				(None, Ok(_)) => Some(Error::new("Test was not reached.", ErrorSource::Internal)),
+			};
+
+			match failure {
+				None => {
+					println!("test {} ... \x1b[32mok\x1b[0m", name);
+					passed += 1;
+				}
+				Some(error) => {
+					println!("test {} ... \x1b[31mFAILED\x1b[0m", name);
+					report(&path, &error, &run.output);
+					failed += 1;
+				}
+			}
+		}
+	}
+
+	let exit_code = print_result(passed, failed);
+	if let Some(coverage) = coverage {
+		crate::write_coverage(&coverage.borrow());
+	}
+	exit_code
+}
+
+/// Expected output of a script: what it printed, followed by the error it
+/// stopped with, if any.
+fn snapshot_of(run: &Run) -> String {
+	let mut snapshot: String = run.output.iter().map(|line| line.clone() + "\n").collect();
+	if let Err(error) = &run.result {
+		snapshot += &format!("error: {}\n", error.message());
+		if let Some(ln) = error.line() {
+			snapshot += &format!("-> line {}\n", ln);
+		}
+	}
+	snapshot
+}
+
+/// Runs each script under `paths` that has a `.out` file next to it and
+/// compares its output with that file. With `bless`, snapshots are written
+/// instead, including for scripts that do not have one yet.
+pub fn snapshot(paths: &[String], includes: &[PathBuf], bless: bool) -> ExitCode {
+	let files = discover_all(paths, &|name| {
+		name.ends_with(".bean") && !name.ends_with("_test.bean")
+	});
+	let mut passed = 0;
+	let mut failed = 0;
+
+	for path in files {
+		let out_path = path.with_extension("out");
+		let expected = fs::read_to_string(&out_path).ok();
+		if expected.is_none() && !bless {
+			continue;
+		}
+
+		let run = match parse_file(&path) {
+			Ok(tree) => run_file(&path, &tree, None, None, includes),
+			Err(error) => Run {
+				result: Err(error),
+				tests: TestRun::default(),
+				output: Vec::new(),
+			},
+		};
+		let actual = snapshot_of(&run);
+
+		if bless {
+			if expected.as_ref() == Some(&actual) {
+				println!("snapshot {} ... \x1b[32mok\x1b[0m", path.display());
+				passed += 1;
+			} else if let Err(error) = fs::write(&out_path, &actual) {
+				println!("snapshot {} ... \x1b[31mFAILED\x1b[0m", path.display());
+				println!("\t\x1b[31;1merror\x1b[0m: Failed to write file. {}", error);
+				failed += 1;
+			} else {
+				println!("snapshot {} ... \x1b[33mblessed\x1b[0m", path.display());
+				passed += 1;
+			}
+		} else if expected.as_ref() == Some(&actual) {
+			println!("snapshot {} ... \x1b[32mok\x1b[0m", path.display());
+			passed += 1;
+		} else {
+			println!("snapshot {} ... \x1b[31mFAILED\x1b[0m", path.display());
+			print_lines(diff_lines(&expected.unwrap_or_default(), &actual).lines());
+			failed += 1;
+		}
+	}
+
+	if passed + failed == 0 {
+		println!("No snapshots found. Use --bless to create them.");
+		return ExitCode::SUCCESS;
+	}
+	print_result(passed, failed)
+}
+
+fn discover_all(paths: &[String], filter: &impl Fn(&str) -> bool) -> Vec<PathBuf> {
+	let mut files = Vec::new();
+	if paths.is_empty() {
+		discover(Path::new("."), filter, &mut files);
+	}
+	for path in paths {
+		discover(Path::new(path), filter, &mut files);
+	}
+	files
+}
+
+fn print_result(passed: usize, failed: usize) -> ExitCode {
+	if failed == 0 {
+		println!("\ntest result: \x1b[32mok\x1b[0m. {} passed; 0 failed", passed);
+		ExitCode::SUCCESS
+	} else {
+		println!(
+			"\ntest result: \x1b[31mFAILED\x1b[0m. {} passed; {} failed",
+			passed, failed
+		);
+		ExitCode::FAILURE
+	}
+}
@@ -0,0 +1,290 @@
+use std::{
+	any::Any,
+	collections::HashMap,
+	fmt::Debug,
+	marker::PhantomData,
+	ops::{Deref, DerefMut},
+};
+
+use crate::{
+	convert::{FromData, IntoData, Receiver, TypedMethod},
+	data::{Data, DataType},
+	error::Error,
+	scope::{block_scope::IfState, function::Function, Scope, ScopeRef},
+	util::{DynAny, Lock, MaybeSync, ReadGuard, Shared, WriteGuard},
+};
+
+#[cfg(not(feature = "sync"))]
+type Method = Shared<dyn Fn(&UserData, Vec<Data>) -> Result<Data, Error>>;
+#[cfg(feature = "sync")]
+type Method = Shared<dyn Fn(&UserData, Vec<Data>) -> Result<Data, Error> + Send + Sync>;
+
+/// Name and methods shared by host values of one kind. Scripts call the
+/// methods with the dot operator, as in `file.read()`.
+pub struct UserType {
+	name: String,
+	methods: HashMap<String, Method>,
+}
+
+impl UserType {
+	pub fn new<F>(name: &str, constructor: F) -> Shared<Self>
+	where
+		F: FnOnce(&mut UserType),
+	{
+		let mut user_type = UserType {
+			name: String::from(name),
+			methods: HashMap::new(),
+		};
+		constructor(&mut user_type);
+		Shared::new(user_type)
+	}
+
+	/// Adds a method taking `&T` or `&mut T`, where `T` is the type of the
+	/// host values, followed by arguments converted with
+	/// [`FromData`](crate::convert::FromData).
+	pub fn method<T, Args, F>(&mut self, name: &str, method: F) -> &mut Self
+	where
+		T: 'static,
+		F: TypedMethod<T, Args> + MaybeSync + 'static,
+	{
+		let fn_name = format!("{}.{}", self.name, name);
+		self.methods.insert(
+			String::from(name),
+			Shared::new(move |this, args| method.call_method(&fn_name, this, args)),
+		);
+		self
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+}
+
+impl Debug for UserType {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("UserType").field("name", &self.name).finish()
+	}
+}
+
+/// An opaque host value, such as a file handle, passed through scripts.
+/// Clones refer to the same value.
+#[derive(Clone, Debug)]
+pub struct UserData {
+	value: Shared<Lock<DynAny>>,
+	user_type: Shared<UserType>,
+}
+
+impl UserData {
+	pub fn new<T: Any + MaybeSync>(value: T, user_type: &Shared<UserType>) -> Self {
+		Self {
+			value: Shared::new(Lock::new(value)),
+			user_type: Shared::clone(user_type),
+		}
+	}
+
+	pub fn user_type(&self) -> &Shared<UserType> {
+		&self.user_type
+	}
+
+	pub fn is<T: Any>(&self) -> bool {
+		self.value.borrow().is::<T>()
+	}
+
+	/// Borrows the value as `T`, or returns `None` if it has another type.
+	///
+	/// # Panics
+	/// Panics if the value is mutably borrowed.
+	pub fn borrow<T: Any>(&self) -> Option<ValueRef<'_, T>> {
+		let guard = self.value.borrow();
+		guard.is::<T>().then_some(ValueRef {
+			guard,
+			marker: PhantomData,
+		})
+	}
+
+	/// Mutably borrows the value as `T`, or returns `None` if it has another
+	/// type.
+	///
+	/// # Panics
+	/// Panics if the value is already borrowed.
+	pub fn borrow_mut<T: Any>(&self) -> Option<ValueMut<'_, T>> {
+		let guard = self.value.borrow_mut();
+		guard.is::<T>().then_some(ValueMut {
+			guard,
+			marker: PhantomData,
+		})
+	}
+
+	pub fn ptr_eq(&self, other: &UserData) -> bool {
+		Shared::ptr_eq(&self.value, &other.value)
+	}
+}
+
+/// Borrowed userdata value, from [`UserData::borrow`].
+pub struct ValueRef<'a, T> {
+	guard: ReadGuard<'a, DynAny>,
+	marker: PhantomData<T>,
+}
+
+impl<T: Any> Deref for ValueRef<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		self.guard.downcast_ref().expect("Type was checked when borrowed.")
+	}
+}
+
+/// Mutably borrowed userdata value, from [`UserData::borrow_mut`].
+pub struct ValueMut<'a, T> {
+	guard: WriteGuard<'a, DynAny>,
+	marker: PhantomData<T>,
+}
+
+impl<T: Any> Deref for ValueMut<'_, T> {
+	type Target = T;
+
+	fn deref(&self) -> &T {
+		self.guard.downcast_ref().expect("Type was checked when borrowed.")
+	}
+}
+
+impl<T: Any> DerefMut for ValueMut<'_, T> {
+	fn deref_mut(&mut self) -> &mut T {
+		self.guard.downcast_mut().expect("Type was checked when borrowed.")
+	}
+}
+
+impl Receiver for UserData {
+	fn with_ref<S: 'static, R>(&self, f: impl FnOnce(&S) -> R) -> Option<R> {
+		self.borrow::<S>().map(|this| f(&this))
+	}
+
+	fn with_mut<S: 'static, R>(&self, f: impl FnOnce(&mut S) -> R) -> Option<R> {
+		self.borrow_mut::<S>().map(|mut this| f(&mut this))
+	}
+}
+
+impl IntoData for UserData {
+	fn into_data(self) -> Data {
+		Data::UserData(self)
+	}
+}
+
+impl FromData for UserData {
+	fn data_type() -> DataType {
+		DataType::UserData
+	}
+
+	fn from_data(data: Data) -> Result<Self, Error> {
+		match data {
+			Data::UserData(v) => Ok(v),
+			data => Err(crate::convert::type_error::<Self>(&data)),
+		}
+	}
+}
+
+/// Scope the dot operator looks up userdata methods in.
+#[derive(Debug)]
+pub(crate) struct MethodScope {
+	this: UserData,
+}
+
+impl Scope for MethodScope {
+	fn has_function(&self, name: &str) -> bool {
+		self.this.user_type.methods.contains_key(name)
+	}
+
+	fn get_function(&self, name: &str) -> Option<Function> {
+		let method = Shared::clone(self.this.user_type.methods.get(name)?);
+		let this = self.this.clone();
+		Some(Function::BuiltIn {
+			callback: Shared::new(move |args, _body_fn, _scope| method(&this, args)),
+		})
+	}
+
+	fn set_function(&mut self, _name: &str, _function: Function) {}
+
+	fn delete_function(&mut self, _name: &str) {}
+
+	fn set_return_value(&mut self, _value: Data) {}
+
+	fn set_if_state(&mut self, _state: IfState) {}
+
+	fn get_function_list(&self) -> HashMap<String, Function> {
+		self.this
+			.user_type
+			.methods
+			.keys()
+			.filter_map(|name| Some((name.clone(), self.get_function(name)?)))
+			.collect()
+	}
+
+	fn as_any(&self) -> &dyn Any {
+		self
+	}
+
+	fn as_mut(&mut self) -> &mut dyn Any {
+		self
+	}
+
+	fn to_string(&self) -> String {
+		format!("[{}]", self.this.user_type.name)
+	}
+}
+
+/// Wraps a userdata value so the dot operator can look up its methods like
+/// the functions of a scope.
+pub(crate) fn method_scope(this: UserData) -> ScopeRef {
+	Shared::new(Lock::new(MethodScope { this }))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::Engine;
+
+	struct Counter {
+		count: u32,
+	}
+
+	fn counter_type() -> Shared<UserType> {
+		UserType::new("Counter", |t| {
+			t.method("get", |this: &Counter| this.count);
+			t.method("add", |this: &mut Counter, by: Option<u32>| {
+				this.count += by.unwrap_or(1);
+				this.count
+			});
+		})
+	}
+
+	#[test]
+	fn borrow_checks_the_type() {
+		let counter = UserData::new(Counter { count: 2 }, &counter_type());
+		assert!(counter.is::<Counter>());
+		assert!(!counter.is::<String>());
+		assert_eq!(counter.borrow::<Counter>().unwrap().count, 2);
+		assert!(counter.borrow::<String>().is_none());
+
+		counter.borrow_mut::<Counter>().unwrap().count = 5;
+		assert_eq!(counter.borrow::<Counter>().unwrap().count, 5);
+		assert!(counter.ptr_eq(&counter.clone()));
+		assert_eq!(counter.user_type().name(), "Counter");
+	}
+
+	#[test]
+	fn methods_are_called_from_scripts() {
+		let counter = UserData::new(Counter { count: 0 }, &counter_type());
+		let engine = Engine::new();
+		engine.set_global("counter", Data::UserData(counter.clone()));
+
+		assert_eq!(
+			engine.eval_str("counter.add()\ncounter.add(3)").unwrap(),
+			Data::Number(4.0)
+		);
+		assert_eq!(engine.eval_str("counter.get()").unwrap(), Data::Number(4.0));
+		assert_eq!(counter.borrow::<Counter>().unwrap().count, 4);
+
+		assert!(engine.eval_str("counter.add(\"x\")").is_err());
+		assert!(engine.eval_str("counter.missing()").is_err());
+	}
+}
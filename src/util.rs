@@ -1,4 +1,13 @@
-use std::{cell::RefCell, rc::Rc};
+use std::any::Any;
+#[cfg(not(feature = "sync"))]
+use std::{
+	cell::{Ref, RefCell, RefMut},
+	rc::Rc,
+};
+#[cfg(feature = "sync")]
+use std::sync::{
+	Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockResult,
+};
 
 #[macro_export]
 macro_rules! arg_check {
@@ -43,8 +52,129 @@ macro_rules! as_mut_type {
 	};
 }
 
+/// Reference-counted pointer used for values shared by the interpreter. An
+/// `Rc`, or an `Arc` with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub type Shared<T> = Rc<T>;
+#[cfg(feature = "sync")]
+pub type Shared<T> = Arc<T>;
+
+/// Weak counterpart of [`Shared`].
+#[cfg(not(feature = "sync"))]
+pub type Weak<T> = std::rc::Weak<T>;
+#[cfg(feature = "sync")]
+pub type Weak<T> = std::sync::Weak<T>;
+
+/// Interior mutability used for values shared by the interpreter. A
+/// `RefCell`, or a read-write lock with the same interface with the `sync`
+/// feature.
+#[cfg(not(feature = "sync"))]
+pub type Lock<T> = RefCell<T>;
+
+/// Guards returned when borrowing a [`Lock`].
+#[cfg(not(feature = "sync"))]
+pub type ReadGuard<'a, T> = Ref<'a, T>;
+#[cfg(not(feature = "sync"))]
+pub type WriteGuard<'a, T> = RefMut<'a, T>;
+#[cfg(feature = "sync")]
+pub type ReadGuard<'a, T> = RwLockReadGuard<'a, T>;
+#[cfg(feature = "sync")]
+pub type WriteGuard<'a, T> = RwLockWriteGuard<'a, T>;
+
+/// Read-write lock with the borrowing interface of a `RefCell`. Locks are
+/// not reentrant, so borrowing a value mutably while it is borrowed on the
+/// same thread blocks instead of panicking.
+#[cfg(feature = "sync")]
+#[derive(Debug, Default)]
+pub struct Lock<T: ?Sized>(RwLock<T>);
+
+#[cfg(feature = "sync")]
+impl<T> Lock<T> {
+	pub fn new(value: T) -> Self {
+		Self(RwLock::new(value))
+	}
+
+	pub fn into_inner(self) -> T {
+		self.0.into_inner().unwrap_or_else(PoisonError::into_inner)
+	}
+}
+
+#[cfg(feature = "sync")]
+impl<T: ?Sized> Lock<T> {
+	pub fn borrow(&self) -> ReadGuard<'_, T> {
+		self.0.read().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	pub fn borrow_mut(&self) -> WriteGuard<'_, T> {
+		self.0.write().unwrap_or_else(PoisonError::into_inner)
+	}
+
+	pub fn try_borrow(&self) -> TryLockResult<ReadGuard<'_, T>> {
+		self.0.try_read()
+	}
+
+	pub fn try_borrow_mut(&self) -> TryLockResult<WriteGuard<'_, T>> {
+		self.0.try_write()
+	}
+}
+
+/// Bound on values shared by the interpreter, which must be `Send` and
+/// `Sync` with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub trait MaybeSync {}
+#[cfg(not(feature = "sync"))]
+impl<T: ?Sized> MaybeSync for T {}
+
+#[cfg(feature = "sync")]
+pub trait MaybeSync: Send + Sync {}
+#[cfg(feature = "sync")]
+impl<T: Send + Sync + ?Sized> MaybeSync for T {}
+
+/// Type-erased value, which is `Send` and `Sync` with the `sync` feature.
+#[cfg(not(feature = "sync"))]
+pub type DynAny = dyn Any;
+#[cfg(feature = "sync")]
+pub type DynAny = dyn Any + Send + Sync;
+
 pub fn make_ref<T>(scope: T) -> MutRc<T> {
-	Rc::new(RefCell::new(scope))
+	Shared::new(Lock::new(scope))
 }
 
-pub type MutRc<T> = Rc<RefCell<T>>;
+pub type MutRc<T> = Shared<Lock<T>>;
+
+/// Compares two texts line by line. Lines only in `expected` are prefixed
+/// with `-`, lines only in `actual` with `+`, and shared lines with a space.
+pub fn diff_lines(expected: &str, actual: &str) -> String {
+	let expected: Vec<&str> = expected.lines().collect();
+	let actual: Vec<&str> = actual.lines().collect();
+
+	// Length of the longest common subsequence of the remaining lines.
+	let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+	for i in (0..expected.len()).rev() {
+		for j in (0..actual.len()).rev() {
+			common[i][j] = if expected[i] == actual[j] {
+				common[i + 1][j + 1] + 1
+			} else {
+				common[i + 1][j].max(common[i][j + 1])
+			};
+		}
+	}
+
+	let mut diff = String::new();
+	let (mut i, mut j) = (0, 0);
+	while i < expected.len() || j < actual.len() {
+		if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+			diff += &format!("  {}\n", expected[i]);
+			i += 1;
+			j += 1;
+		} else if i < expected.len() && (j == actual.len() || common[i + 1][j] >= common[i][j + 1])
+		{
+			diff += &format!("- {}\n", expected[i]);
+			i += 1;
+		} else {
+			diff += &format!("+ {}\n", actual[j]);
+			j += 1;
+		}
+	}
+	diff
+}
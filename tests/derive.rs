@@ -0,0 +1,62 @@
+#![cfg(feature = "derive")]
+
+use bean_script::{convert::IntoData, data::Data, engine::Engine, BeanScope};
+
+#[derive(Debug, BeanScope)]
+#[bean(method(area), method(scale = "grow"))]
+struct Rect {
+	#[bean(field)]
+	width: f64,
+	#[bean(field = "h")]
+	height: f64,
+}
+
+impl Rect {
+	fn area(&self) -> f64 {
+		self.width * self.height
+	}
+
+	fn scale(&mut self, by: f64) {
+		self.width *= by;
+		self.height *= by;
+	}
+}
+
+fn engine() -> Engine {
+	let engine = Engine::new();
+	let rect = Rect {
+		width: 2.0,
+		height: 3.0,
+	};
+	engine.set_global("rect", rect.into_data());
+	engine
+}
+
+#[test]
+fn fields_get_and_set() {
+	let engine = engine();
+	assert_eq!(engine.eval_str("rect.width").unwrap(), Data::Number(2.0));
+	assert_eq!(engine.eval_str("rect.h").unwrap(), Data::Number(3.0));
+	assert_eq!(engine.eval_str("rect.width(5)").unwrap(), Data::Number(2.0));
+	assert_eq!(engine.eval_str("rect.width").unwrap(), Data::Number(5.0));
+	assert!(engine.eval_str("rect.width(\"wide\")").is_err());
+	assert!(engine.eval_str("rect.height").is_err());
+}
+
+#[test]
+fn methods_are_called() {
+	let engine = engine();
+	assert_eq!(engine.eval_str("rect.area").unwrap(), Data::Number(6.0));
+	engine.eval_str("rect.grow(2)").unwrap();
+	assert_eq!(engine.eval_str("rect.area").unwrap(), Data::Number(24.0));
+	assert!(engine.eval_str("rect.grow()").is_err());
+}
+
+#[test]
+fn prints_fields() {
+	let engine = engine();
+	assert_eq!(
+		engine.eval_str("str(rect)").unwrap(),
+		Data::String(String::from("[Rect width: 2, h: 3]"))
+	);
+}